@@ -0,0 +1,517 @@
+use terr::heightmap::Heightmap;
+
+use rand::prelude::*; // Random
+use std::collections::VecDeque; // Distance to water, nearest first
+use std::f32::consts::PI;
+use std::path::Path;
+
+use crate::config::{TerrainConfig, ClimateConfig, Wind};
+use crate::error::Error;
+use crate::grid::neighbors;
+use crate::heightmap::apply_simplex;
+use crate::render::test_png_hm;
+use crate::tile::{Tilemap, TileCategory};
+
+// Temperature in degrees and moisture from 0 to 100 for every tile
+pub struct Climate {
+    pub temperature: Heightmap<f32>,
+    pub moisture: Heightmap<f32>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Biome {
+    Tundra,
+    Desert,
+    Grassland,
+    Shrubland,
+    Woodland,
+    Forest,
+}
+
+use Biome::*;
+
+// Whittaker-style biome table. Rows are cold, temperate and hot, columns 
+// are moisture bands of 20 from dry to wet.
+const WHITTAKER: [[Biome; 5]; 3] = [
+    [Tundra, Tundra, Shrubland, Forest, Forest],
+    [Desert, Grassland, Shrubland, Woodland, Forest],
+    [Desert, Grassland, Grassland, Woodland, Forest],
+];
+
+// Warm at the equator in the middle of the map, cold at the top and bottom 
+// edges and cooling with height above the sea. The cosine keeps it seamless 
+// when the map wraps, noise keeps biome edges from running straight across.
+fn temperature_map<R: Rng>(
+    heightmap: &Heightmap<f32>, 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    terrain: &TerrainConfig, 
+    climate: &ClimateConfig, 
+    rng: &mut R
+) -> Heightmap<f32> {
+
+    let mut noise = Heightmap::new_flat((width, height), (0.0, 0.0));
+    apply_simplex(&mut noise, width, height, wrap, climate.noise_scale, rng.gen());
+
+    let mut temperature = Heightmap::new_flat((width, height), (0.0, 0.0));
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let latitude = (1.0 - (2.0 * PI * y as f32 / height as f32).cos()) / 2.0;
+            let elevation = (heightmap.get(x, y) - terrain.cutoff_water).max(0.0);
+
+            let roughness = (noise.get(x, y) - 50.0) / 50.0 * climate.temperature_noise;
+
+            let t = climate.pole_temperature + (climate.equator_temperature - climate.pole_temperature) * latitude
+                - elevation * climate.lapse_rate + roughness;
+
+            temperature.set(x, y, t);
+        }
+    }
+
+    temperature
+}
+
+// Tiles from the nearest water, counted in steps
+fn water_distance(tilemap: &Tilemap, width: u32, height: u32, wrap: bool) -> Vec<Vec<u32>> {
+
+    let mut dist = vec![vec![u32::MAX; height as usize]; width as usize];
+    let mut queue = VecDeque::new();
+
+    for x in 0..width {
+        for y in 0..height {
+            if tilemap.get(x, y).cat == TileCategory::Water {
+                dist[x as usize][y as usize] = 0;
+                queue.push_back((x, y));
+            }
+        }
+    }
+
+    while let Some((x, y)) = queue.pop_front() {
+
+        let d = dist[x as usize][y as usize];
+
+        for (nx, ny) in neighbors(x, y, width, height, wrap) {
+            if dist[nx as usize][ny as usize] == u32::MAX {
+                dist[nx as usize][ny as usize] = d + 1;
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+
+    dist
+}
+
+// Wind carries moisture in from the sea. It rains a little on every land 
+// tile it crosses and a lot where it has to climb, so the far side of a 
+// mountain range stays dry.
+fn wind_moisture(
+    tilemap: &Tilemap, 
+    heightmap: &Heightmap<f32>, 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    terrain: &TerrainConfig, 
+    climate: &ClimateConfig
+) -> Heightmap<f32> {
+
+    let mut moisture = Heightmap::new_flat((width, height), (0.0, 0.0));
+
+    // Lines of tiles in the direction the wind blows
+    let (lines, length) = match climate.wind {
+        Wind::West | Wind::East => (height, width),
+        Wind::North | Wind::South => (width, height),
+    };
+
+    let coor = |line: u32, step: u32| match climate.wind {
+        Wind::West => (step, line),
+        Wind::East => (width - 1 - step, line),
+        Wind::North => (line, step),
+        Wind::South => (line, height - 1 - step),
+    };
+
+    // On a wrapping world the wind comes around from the far side, so go 
+    // around twice and keep the second lap
+    let laps = if wrap { 2 } else { 1 };
+
+    for line in 0..lines {
+
+        let mut humidity = 1.0;
+        let mut last_height = terrain.cutoff_water;
+
+        for lap in 0..laps {
+            for step in 0..length {
+
+                let (x, y) = coor(line, step);
+                let h = heightmap.get(x, y).max(terrain.cutoff_water);
+
+                if tilemap.get(x, y).cat == TileCategory::Water {
+                    humidity += (1.0 - humidity) * 0.2;
+                } else {
+                    humidity *= 1.0 - climate.rainfall;
+                    humidity -= (h - last_height).max(0.0) * climate.rain_shadow;
+                    humidity = humidity.max(0.0);
+                }
+
+                last_height = h;
+
+                if lap == laps - 1 {
+                    moisture.set(x, y, humidity);
+                }
+            }
+        }
+    }
+
+    moisture
+}
+
+// Averages every cell with those up to "radius" tiles away along both 
+// axes, so neighbouring lines of wind don't leave streaks
+fn blur(map: &Heightmap<f32>, width: u32, height: u32, wrap: bool, radius: i32) -> Heightmap<f32> {
+
+    let mut blurred = Heightmap::new_flat((width, height), (0.0, 0.0));
+
+    let wrap_coor = |v: i32, size: u32| if wrap {
+        v.rem_euclid(size as i32) as u32
+    } else {
+        v.clamp(0, size as i32 - 1) as u32
+    };
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let mut sum = 0.0;
+
+            for dx in -radius..=radius {
+                for dy in -radius..=radius {
+                    sum += map.get(wrap_coor(x as i32 + dx, width), wrap_coor(y as i32 + dy, height));
+                }
+            }
+
+            blurred.set(x, y, sum / ((radius * 2 + 1) * (radius * 2 + 1)) as f32);
+        }
+    }
+
+    blurred
+}
+
+// Moisture from the wind and from nearby water, roughened with noise
+fn moisture_map<R: Rng>(
+    tilemap: &Tilemap, 
+    heightmap: &Heightmap<f32>, 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    terrain: &TerrainConfig, 
+    climate: &ClimateConfig, 
+    rng: &mut R
+) -> Heightmap<f32> {
+
+    let wind = blur(&wind_moisture(tilemap, heightmap, width, height, wrap, terrain, climate), width, height, wrap, 6);
+    let dist = water_distance(tilemap, width, height, wrap);
+
+    let mut noise = Heightmap::new_flat((width, height), (0.0, 0.0));
+    apply_simplex(&mut noise, width, height, wrap, climate.noise_scale, rng.gen());
+
+    let mut moisture = Heightmap::new_flat((width, height), (0.0, 0.0));
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let near_water = (-(dist[x as usize][y as usize] as f32) / climate.water_reach).exp();
+            let roughness = (noise.get(x, y) - 50.0) / 50.0 * climate.moisture_noise;
+
+            let m = (wind.get(x, y) * 50.0 + near_water * 35.0 + roughness).clamp(0.0, 100.0);
+
+            moisture.set(x, y, m);
+        }
+    }
+
+    moisture
+}
+
+// Temperature and moisture for the finished terrain, including lakes and 
+// rivers
+pub fn compute_climate<R: Rng>(
+    tilemap: &Tilemap, 
+    heightmap: &Heightmap<f32>, 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    terrain: &TerrainConfig, 
+    climate: &ClimateConfig, 
+    rng: &mut R, 
+    debug_dir: Option<&Path>
+) -> Result<Climate, Error> {
+
+    let moisture = moisture_map(tilemap, heightmap, width, height, wrap, terrain, climate, rng);
+    let temperature = temperature_map(heightmap, width, height, wrap, terrain, climate, rng);
+
+    if let Some(dir) = debug_dir {
+
+        // Pole temperature is black, equator white
+        let mut scaled = Heightmap::new_flat((width, height), (0.0, 0.0));
+        let span = climate.equator_temperature - climate.pole_temperature;
+
+        for x in 0..width {
+            for y in 0..height {
+                let t = (temperature.get(x, y) - climate.pole_temperature) / span;
+                scaled.set(x, y, t.clamp(0.0, 0.99) * 100.0);
+            }
+        }
+
+        test_png_hm(&scaled, width, height, dir, "test12")?;
+        test_png_hm(&moisture, width, height, dir, "test13")?;
+    }
+
+    Ok(Climate { temperature, moisture })
+}
+
+// Snow, ice or frozen water for a tile below the snow temperature, or None 
+// if it stays as it is
+fn frozen_tile(name: &str, biome: Biome, t: f32, climate: &ClimateConfig) -> Option<&'static str> {
+
+    match name {
+        "water_0000" if t < climate.freeze_below => Some("frozen_water"),
+        "water_0000" => None,
+        "grass" | "sand_0000" if t < climate.ice_below => Some("ice"),
+        "grass" if biome == Forest || biome == Woodland => Some("snow_forest"),
+        "grass" | "sand_0000" => Some("snow_0000"),
+        "hill_grass" | "hill_sand" => Some("hill_snow"),
+        "mountain_grass" | "mountain_sand" => Some("mountain_snow"),
+        _ => None,
+    }
+}
+
+// Snow, ice and tundra for maps painted with noise instead of the climate 
+// model. Temperature comes from latitude and height the same way, so the 
+// poles look alike either way, and the noise biomes are kept where it is 
+// warmer. Forests stay forests in the tundra.
+pub fn paint_polar<R: Rng>(
+    tilemap: &mut Tilemap, 
+    heightmap: &Heightmap<f32>, 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    terrain: &TerrainConfig, 
+    climate: &ClimateConfig, 
+    rng: &mut R
+) {
+
+    let temperature = temperature_map(heightmap, width, height, wrap, terrain, climate, rng);
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let tile = tilemap.get(x, y);
+            let t = temperature.get(x, y);
+
+            // Ground cover from the noise biomes freezes like plain grass
+            let (name, biome) = match tile.name {
+                "forest" => ("grass", Forest),
+                "flowers" | "thick_grass" | "thicker_grass" | "swamp" => ("grass", Grassland),
+                name => (name, Grassland),
+            };
+
+            if t < climate.snow_below || tile.cat == TileCategory::Water {
+                if let Some(frozen) = frozen_tile(name, biome, t, climate) {
+                    tilemap.set_by_name(x, y, frozen);
+                }
+            } else if t < climate.cold_below && ["grass", "flowers", "thick_grass", "thicker_grass"].contains(&tile.name) {
+                tilemap.set_by_name(x, y, "tundra");
+            }
+        }
+    }
+}
+
+// Biomes from temperature and moisture instead of noise. Deserts end up in 
+// rain shadows and far inland, swamps in wet lowlands along rivers and lakes, 
+// snow and ice toward the poles and on high peaks.
+pub fn paint_climate<R: Rng>(
+    tilemap: &mut Tilemap, 
+    heightmap: &Heightmap<f32>, 
+    climate_map: &Climate, 
+    width: u32, 
+    height: u32, 
+    terrain: &TerrainConfig, 
+    climate: &ClimateConfig, 
+    rng: &mut R
+) {
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let tile = tilemap.get(x, y);
+            let t = climate_map.temperature.get(x, y);
+            let m = climate_map.moisture.get(x, y);
+            let r_num = rng.gen_range(1, 1001);
+
+            let row = if t < climate.cold_below {
+                0
+            } else if t > climate.hot_above {
+                2
+            } else {
+                1
+            };
+            let column = ((m / 20.0) as usize).min(4);
+
+            // Water only freezes when it is colder than where snow falls
+            if t < climate.snow_below || tile.cat == TileCategory::Water {
+                if let Some(name) = frozen_tile(tile.name, WHITTAKER[row][column], t, climate) {
+                    tilemap.set_by_name(x, y, name);
+                }
+                continue;
+            }
+
+            let lowland = heightmap.get(x, y) < terrain.cutoff_water + climate.swamp_lowland;
+
+            if row > 0 && lowland && m >= climate.swamp_moisture {
+                if tile.name == "grass" {
+                    tilemap.set_by_name(x, y, "swamp");
+                }
+                continue;
+            }
+
+            match WHITTAKER[row][column] {
+                Tundra => {
+                    if tile.name == "grass" {
+                        tilemap.set_by_name(x, y, "tundra");
+                    }
+                }
+                Desert => {
+                    if tile.name == "grass" {
+                        tilemap.set_by_name(x, y, "sand_0000");
+                    } else if tile.name == "hill_grass" {
+                        tilemap.set_by_name(x, y, "hill_sand");
+                    } else if tile.name == "mountain_grass" {
+                        tilemap.set_by_name(x, y, "mountain_sand");
+                    }
+                }
+                Grassland => {
+                    if tile.name == "grass" && r_num > 950 {
+                        tilemap.set_by_name(x, y, "flowers");
+                    }
+                }
+                Shrubland => {
+                    if tile.name == "grass" {
+                        if r_num > 500 {
+                            tilemap.set_by_name(x, y, "thick_grass");
+                        } else if r_num > 250 {
+                            tilemap.set_by_name(x, y, "flowers");
+                        }
+                    }
+                }
+                Woodland => {
+                    if tile.name == "grass" {
+                        if r_num > 250 {
+                            tilemap.set_by_name(x, y, "thicker_grass");
+                        } else if r_num > 63 {
+                            tilemap.set_by_name(x, y, "thick_grass");
+                        } else if r_num > 16 {
+                            tilemap.set_by_name(x, y, "flowers");
+                        }
+                    }
+                }
+                Forest => {
+                    if tile.name == "grass" {
+                        tilemap.set_by_name(x, y, "forest");
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::tile::Tilelist;
+    use rand_chacha::ChaCha8Rng;
+    use std::sync::Arc;
+
+    // Sea on the west edge and low land east of it, with or without a ridge 
+    // across the middle
+    fn coast(ridge: bool) -> (Tilemap, Heightmap<f32>) {
+
+        let (width, height) = (30, 8);
+        let mut tilemap = Tilemap::new(width, height, "grass", &Arc::new(Tilelist::old_school()));
+        let mut heightmap = Heightmap::new_flat((width, height), (0.0, 0.0));
+
+        for x in 0..width {
+            for y in 0..height {
+
+                let h = if x < 4 {
+                    tilemap.set_by_name(x, y, "water_0000");
+                    40.0
+                } else if ridge && (15..17).contains(&x) {
+                    75.0
+                } else {
+                    60.0
+                };
+
+                heightmap.set(x, y, h);
+            }
+        }
+
+        (tilemap, heightmap)
+    }
+
+    // Snow at the top and bottom edges without the climate model, the 
+    // noise biomes left alone at the equator
+    #[test]
+    fn polar_edges_freeze() {
+
+        let (width, height) = (20, 40);
+        let mut tilemap = Tilemap::new(width, height, "grass", &Arc::new(Tilelist::old_school()));
+        let mut heightmap = Heightmap::new_flat((width, height), (0.0, 0.0));
+
+        for x in 0..width {
+            for y in 0..height {
+                heightmap.set(x, y, 60.0);
+            }
+            tilemap.set_by_name(x, height / 2, "forest");
+        }
+
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+
+        paint_polar(&mut tilemap, &heightmap, width, height, false, &TerrainConfig::default(), &ClimateConfig::default(), &mut rng);
+
+        for x in 0..width {
+
+            for &y in [0, height - 1].iter() {
+                let tile = tilemap.get(x, y);
+                assert!(tile.cat == TileCategory::Snow || tile.cat == TileCategory::Ice, "{} at {:?}", tile.name, (x, y));
+            }
+
+            assert_eq!(tilemap.get(x, height / 2).name, "forest");
+            assert_eq!(tilemap.get(x, height / 2 + 1).name, "grass");
+        }
+    }
+
+    #[test]
+    fn rain_shadow_behind_ridge_is_drier() {
+
+        let terrain = TerrainConfig::default();
+        let climate = ClimateConfig { wind: Wind::West, ..ClimateConfig::default() };
+
+        let (flat_tiles, flat_heights) = coast(false);
+        let (ridge_tiles, ridge_heights) = coast(true);
+
+        let flat = wind_moisture(&flat_tiles, &flat_heights, 30, 8, false, &terrain, &climate);
+        let ridge = wind_moisture(&ridge_tiles, &ridge_heights, 30, 8, false, &terrain, &climate);
+
+        for y in 0..8 {
+
+            // Upwind of the ridge nothing changes
+            assert_eq!(ridge.get(12, y), flat.get(12, y));
+
+            // Downwind it is drier than the same spot without the ridge, 
+            // and drier than upwind of it
+            assert!(ridge.get(20, y) < flat.get(20, y));
+            assert!(ridge.get(20, y) < ridge.get(12, y));
+        }
+    }
+}
@@ -0,0 +1,860 @@
+use serde::{Serialize, Deserialize}; // Reading and writing config files
+use std::fs; // Filesystem
+use std::path::{Path, PathBuf};
+
+use crate::HEIGHTMAP_RANGE;
+use crate::error::Error;
+
+// Settings for a single run of the generator. Everything except the seed 
+// and debug output can be loaded from a TOML file, see "default_config.toml"
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    #[serde(skip)]
+    pub seed: u64, // Master seed, every random decision is derived from it
+    pub width: u32,
+    pub height: u32,
+    pub wrap: bool, // Seamless in both directions, the map tiles like a torus instead of being one island
+    pub erode: bool, // Carves valleys and ridges into the heightmap
+    pub climate: bool, // Biomes from temperature and moisture instead of noise
+    pub polar: bool, // Snow, tundra and ice toward the top and bottom edges when climate is off
+    pub depths: bool, // Shallows, reefs and deep ocean instead of one kind of sea
+    pub rivers: bool,
+    pub lakes: bool,
+    pub bridges: bool, // Only has an effect when rivers are enabled
+    pub caves: bool,
+    pub towns: bool,
+    pub roads: bool, // Connects towns, with bridges only where roads cross rivers
+    pub repair: bool, // Makes every town, castle and cave reachable on foot
+    pub dungeons: bool, // A dungeon behind every cave
+    pub interiors: bool, // A walled inside for every town and castle
+    pub names: bool, // Names for sites, landmasses, mountain ranges, rivers, lakes and seas
+    pub kingdoms: bool, // Every castle rules the land and towns around it
+    #[serde(skip)]
+    pub debug_dir: Option<PathBuf>, // Save intermediate heightmaps here
+    pub terrain: TerrainConfig,
+    pub layout: LayoutConfig,
+    pub erosion: ErosionConfig,
+    pub biomes: BiomeConfig,
+    pub climate_model: ClimateConfig,
+    pub ocean: OceanConfig,
+    pub river: RiverConfig,
+    pub lake: LakeConfig,
+    pub road: RoadConfig,
+    pub connectivity: ConnectivityConfig,
+    pub sites: SiteConfig,
+    pub dungeon: DungeonConfig,
+    pub interior: InteriorConfig,
+    pub naming: NameConfig,
+    pub kingdom: KingdomConfig,
+}
+
+// Heightmap shape and the heights where terrain types change.
+// Heights are in the range 0 to 100.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TerrainConfig {
+    pub cutoff_water: f32, // Below this is water
+    pub cutoff_terrain: f32, // Below this is grass, above is hills
+    pub cutoff_hill: f32, // Above this is mountains
+    pub noise_scale: f64, // Simplex noise blended into the heightmap, smaller is larger blobs
+    pub land_radius: f32, // Radius of the island as a fraction of the map size, other layouts scale from it
+}
+
+// Where the land goes, as a mask the heightmap is multiplied with
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Layout {
+    Island, // One island in the middle
+    Continents, // "count" large landmasses
+    Archipelago, // Many small islands
+    InlandSea, // A ring of land around a sea in the middle
+    Pangaea, // One landmass covering most of the map
+    Ring, // A thin ring of land around open water
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+    pub mode: Layout,
+    pub count: u32, // Continents: number of landmasses, archipelago: 5 islands for each
+    pub size_variance: f32, // Landmass radii vary by up to this fraction
+    pub min_separation: f32, // Between landmass centers, as a fraction of the map size
+    pub mask_image: Option<PathBuf>, // Grayscale image of where land should be, replaces the layout
+    pub mask_strength: f32, // How closely coastlines follow the mask image, 0 to 1
+}
+
+// Hydraulic erosion runs raindrops down the heightmap, thermal erosion 
+// crumbles slopes steeper than the talus. Heights are in the range 0 to 100.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ErosionConfig {
+    pub droplets_per_tile: f32, // Raindrops for hydraulic erosion, 0 turns it off
+    pub droplet_lifetime: u32, // Steps a raindrop takes at most
+    pub hydraulic_strength: f32, // How fast raindrops pick up sediment, 0 to 1
+    pub deposition: f32, // How fast raindrops drop sediment they can't carry, 0 to 1
+    pub evaporation: f32, // Water lost every step, 0 to 1
+    pub inertia: f32, // How much raindrops keep going straight instead of downhill, 0 to 1
+    pub capacity: f32, // Sediment a raindrop can carry for its speed, water and slope
+    pub thermal_iterations: u32, // Passes of thermal erosion, 0 turns it off
+    pub thermal_strength: f32, // Share of the excess slope that crumbles each pass, 0 to 1
+    pub talus: f32, // Height difference between neighbors that is steep enough to crumble
+}
+
+// Noise bands for forest, grassland and desert, plus swamps and dunes.
+// Noise values are in the range 0 to 100.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BiomeConfig {
+    pub forest_above: f32,
+    pub thicker_grass_above: f32,
+    pub thick_grass_above: f32,
+    pub flowers_above: f32,
+    pub desert_below: f32,
+    pub fine_noise_scale: f64,
+    pub coarse_noise_scale: f64,
+    pub swamp_above: f32,
+    pub swamp_noise_scale: f64,
+    pub dune_chance: f32, // Chance of grass next to water turning to sand
+}
+
+// Direction the prevailing wind blows from
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Wind {
+    West,
+    East,
+    North,
+    South,
+}
+
+// Temperatures are in degrees, moisture in the range 0 to 100. The 
+// equator runs through the middle of the map, the poles along the top 
+// and bottom edges.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClimateConfig {
+    pub equator_temperature: f32,
+    pub pole_temperature: f32,
+    pub lapse_rate: f32, // Degrees colder for every unit of height above the sea
+    pub wind: Wind,
+    pub rainfall: f32, // Share of its moisture the wind loses over each land tile, 0 to 1
+    pub rain_shadow: f32, // Moisture the wind loses for every unit of height it climbs, 0 to 1
+    pub water_reach: f32, // Tiles over which nearby water keeps land moist
+    pub noise_scale: f64,
+    pub temperature_noise: f32, // Degrees added or taken away by noise
+    pub moisture_noise: f32, // Moisture added or taken away by noise
+    pub cold_below: f32, // Temperature below which biomes are cold
+    pub hot_above: f32, // Temperature above which biomes are hot
+    pub snow_below: f32, // Land colder than this is covered in snow
+    pub ice_below: f32, // Flat land colder than this is an ice sheet
+    pub freeze_below: f32, // Water colder than this freezes over
+    pub swamp_moisture: f32, // Lowlands at least this wet become swamp
+    pub swamp_lowland: f32, // Height above the sea that still counts as lowland
+}
+
+// Depth bands of the sea, by heightmap value below the water cutoff
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OceanConfig {
+    pub shallow_above: f32, // Sea above this height is shallow
+    pub deep_below: f32, // Sea below this height is deep ocean
+    pub reef_above: f32, // Reef noise above this turns open shallows into reefs
+    pub reef_noise_scale: f64,
+}
+
+// How rivers find their way to the sea
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RiverMode {
+    RandomWalk, // Heads for the closest water in a straight line, with random turns
+    Downhill, // Follows the steepest descent of the heightmap
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiverConfig {
+    pub mode: RiverMode,
+    pub fill_pits: bool, // Downhill only: fill pits so every river reaches the sea, otherwise they end in a lake
+}
+
+// Which basins in the heightmap fill with water
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LakeConfig {
+    pub frequency: f32, // Chance of a basin becoming a lake
+    pub max_size: u32, // In tiles, larger basins only fill their lowest part and don't overflow
+    pub min_depth: f32, // Shallower basins stay dry
+}
+
+// Road network between towns and castles. Costs are per tile walked 
+// onto, grass costs 1 and mountains can't be crossed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoadConfig {
+    pub neighbors: u32, // Roads are searched to this many nearest sites, for the tree and extra roads
+    pub detour_factor: f32, // Add a road when going around through the network costs this much more
+    pub road_cost: f32, // Existing roads and bridges, the cheapest tile
+    pub sand_cost: f32,
+    pub snow_cost: f32, // Snow and ice
+    pub forest_cost: f32,
+    pub hill_cost: f32,
+    pub swamp_cost: f32,
+    pub bridge_cost: f32, // For each river tile a new bridge spans
+    pub max_bridge_length: u32,
+}
+
+// Repairing sites that can't be reached on foot from the start position
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConnectivityConfig {
+    pub start: Option<(u32, u32)>, // Defaults to a castle, town or cave on the largest landmass
+    pub pass_cost: f32, // For each mountain tile carved into a pass, walking a tile costs 1
+    pub bridge_cost: f32, // For each river tile a new bridge spans
+    pub max_bridge_length: u32,
+    pub relocate: bool, // Move sites that can't be connected, otherwise leave them
+}
+
+// How towns pick where to go
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TownPlacement {
+    Random, // Anywhere on open land, half of them on the coast
+    Scored, // Best scoring spots first, kept apart, every landmass gets some
+}
+
+// Density of towns and how many of them are castles
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SiteConfig {
+    pub bridges_per_river: u32, // Without roads
+    pub bridge_spacing: u32, // Minimum distance between bridges in tiles, counted along both axes
+    pub walkable_tiles_per_town: u32,
+    pub castle_chance_coast: f32,
+    pub castle_chance_inland: f32,
+    pub placement: TownPlacement,
+    pub min_town_distance: f32, // Scored only: in tiles, straight line
+    pub water_weight: f32, // Scored only: for water, rivers and lakes included, within 2 tiles
+    pub flat_weight: f32, // Scored only: for flat land around the town
+    pub fertile_weight: f32, // Scored only: for grassland around the town rather than forest, swamp, desert or snow
+    pub score_jitter: f32, // Scored only: random amount added to every score
+    pub min_landmass_tiles: u32, // Scored only: landmasses with this many walkable tiles get at least one town
+}
+
+// How dungeon floors are laid out
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DungeonStyle {
+    Rooms, // Rectangular rooms joined by corridors
+    Caverns, // Winding caves grown with cellular automata
+    Mixed, // Either, picked for each floor
+}
+
+// Dungeons behind caves, each with one or more floors linked by stairs
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DungeonConfig {
+    pub style: DungeonStyle,
+    pub width: u32, // Floor size in tiles
+    pub height: u32,
+    pub min_floors: u32,
+    pub max_floors: u32,
+    pub rooms: u32, // Rooms style: rooms tried on each floor, overlapping ones are dropped
+    pub room_min: u32, // Rooms style: smallest and largest room side in tiles
+    pub room_max: u32,
+    pub cavern_fill: f32, // Caverns style: share of the floor that starts out as wall
+    pub cavern_steps: u32, // Caverns style: smoothing passes of the cellular automaton
+    pub chests_per_floor: u32,
+}
+
+// Insides of towns and castles. Sizes and building counts go from min to 
+// max with how much land a site serves compared to the busiest one.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InteriorConfig {
+    pub town_min_size: u32, // Side of the square town map in tiles
+    pub town_max_size: u32,
+    pub castle_min_size: u32,
+    pub castle_max_size: u32,
+    pub catchment_radius: u32, // Walkable tiles up to this many steps away count toward the land a site serves
+    pub min_buildings: u32, // Castles get half as many, around the keep
+    pub max_buildings: u32,
+    pub church_above: f32, // Towns at least this important get a church, 0 to 1
+    pub npcs_per_building: f32,
+}
+
+// Which areas are large enough to get a name. Sites always get one.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NameConfig {
+    pub labels: bool, // Also save a copy of the map with names drawn on
+    pub min_landmass_tiles: u32,
+    pub min_range_tiles: u32, // Mountain tiles in one connected range
+    pub min_river_tiles: u32,
+    pub min_sea_tiles: u32,
+    pub ocean_tiles: u32, // Seas at least this large are oceans
+}
+
+// How kingdoms spread from their capitals. Costs are per tile spread onto, 
+// open land costs 1, so expensive tiles end up as borders.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KingdomConfig {
+    pub hill_cost: f32,
+    pub mountain_cost: f32,
+    pub river_cost: f32, // For each river tile crossed, bridges cost 1
+    pub sea_cost: f32, // For each tile of sea or lake, so islands without a castle still go to someone
+    pub strength_variance: f32, // Kingdoms spread up to this fraction cheaper or dearer than average, 0 to 0.9
+    pub tint: f32, // How strongly the political map is colored, 0 to 1
+}
+
+impl Default for Config {
+
+    fn default() -> Self {
+        Config {
+            seed: 0,
+            width: 2_u32.pow(8) + 1,
+            height: 2_u32.pow(8) + 1,
+            wrap: false,
+            erode: false,
+            climate: false,
+            polar: false,
+            depths: false,
+            rivers: true,
+            lakes: false,
+            bridges: true,
+            caves: true,
+            towns: true,
+            roads: false,
+            repair: false,
+            dungeons: false,
+            interiors: false,
+            names: false,
+            kingdoms: false,
+            debug_dir: None,
+            terrain: TerrainConfig::default(),
+            layout: LayoutConfig::default(),
+            erosion: ErosionConfig::default(),
+            biomes: BiomeConfig::default(),
+            climate_model: ClimateConfig::default(),
+            ocean: OceanConfig::default(),
+            river: RiverConfig::default(),
+            lake: LakeConfig::default(),
+            road: RoadConfig::default(),
+            connectivity: ConnectivityConfig::default(),
+            sites: SiteConfig::default(),
+            dungeon: DungeonConfig::default(),
+            interior: InteriorConfig::default(),
+            naming: NameConfig::default(),
+            kingdom: KingdomConfig::default(),
+        }
+    }
+}
+
+impl Default for TerrainConfig {
+
+    fn default() -> Self {
+        TerrainConfig {
+            cutoff_water: 50.0,
+            cutoff_terrain: 80.0,
+            cutoff_hill: 85.0,
+            noise_scale: 0.044,
+            land_radius: 0.32,
+        }
+    }
+}
+
+impl Default for LayoutConfig {
+
+    fn default() -> Self {
+        LayoutConfig {
+            mode: Layout::Island,
+            count: 3,
+            size_variance: 0.3,
+            min_separation: 0.4,
+            mask_image: None,
+            mask_strength: 0.5,
+        }
+    }
+}
+
+impl Default for ErosionConfig {
+
+    fn default() -> Self {
+        ErosionConfig {
+            droplets_per_tile: 1.0,
+            droplet_lifetime: 30,
+            hydraulic_strength: 0.3,
+            deposition: 0.3,
+            evaporation: 0.02,
+            inertia: 0.05,
+            capacity: 4.0,
+            thermal_iterations: 10,
+            thermal_strength: 0.5,
+            talus: 4.0,
+        }
+    }
+}
+
+impl Default for BiomeConfig {
+
+    fn default() -> Self {
+        BiomeConfig {
+            forest_above: 60.0,
+            thicker_grass_above: 50.0,
+            thick_grass_above: 40.0,
+            flowers_above: 30.0,
+            desert_below: 30.0,
+            fine_noise_scale: 0.088,
+            coarse_noise_scale: 0.022,
+            swamp_above: 80.0,
+            swamp_noise_scale: 0.022,
+            dune_chance: 0.75,
+        }
+    }
+}
+
+impl Default for ClimateConfig {
+
+    fn default() -> Self {
+        ClimateConfig {
+            equator_temperature: 30.0,
+            pole_temperature: -10.0,
+            lapse_rate: 0.5,
+            wind: Wind::West,
+            rainfall: 0.005,
+            rain_shadow: 0.02,
+            water_reach: 8.0,
+            noise_scale: 0.03,
+            temperature_noise: 5.0,
+            moisture_noise: 20.0,
+            cold_below: 5.0,
+            hot_above: 22.0,
+            snow_below: -2.0,
+            ice_below: -12.0,
+            freeze_below: -6.0,
+            swamp_moisture: 75.0,
+            swamp_lowland: 5.0,
+        }
+    }
+}
+
+impl Default for OceanConfig {
+
+    fn default() -> Self {
+        OceanConfig {
+            shallow_above: 45.0,
+            deep_below: 30.0,
+            reef_above: 75.0,
+            reef_noise_scale: 0.1,
+        }
+    }
+}
+
+impl Default for RiverConfig {
+
+    fn default() -> Self {
+        RiverConfig {
+            mode: RiverMode::RandomWalk,
+            fill_pits: true,
+        }
+    }
+}
+
+impl Default for LakeConfig {
+
+    fn default() -> Self {
+        LakeConfig {
+            frequency: 0.5,
+            max_size: 300,
+            min_depth: 0.5,
+        }
+    }
+}
+
+impl Default for RoadConfig {
+
+    fn default() -> Self {
+        RoadConfig {
+            neighbors: 4,
+            detour_factor: 1.5,
+            road_cost: 0.5,
+            sand_cost: 1.5,
+            snow_cost: 2.0,
+            forest_cost: 2.0,
+            hill_cost: 3.0,
+            swamp_cost: 4.0,
+            bridge_cost: 5.0,
+            max_bridge_length: 3,
+        }
+    }
+}
+
+impl Default for ConnectivityConfig {
+
+    fn default() -> Self {
+        ConnectivityConfig {
+            start: None,
+            pass_cost: 10.0,
+            bridge_cost: 5.0,
+            max_bridge_length: 3,
+            relocate: true,
+        }
+    }
+}
+
+impl Default for SiteConfig {
+
+    fn default() -> Self {
+        SiteConfig {
+            bridges_per_river: 3,
+            bridge_spacing: 3,
+            walkable_tiles_per_town: 400,
+            castle_chance_coast: 0.10,
+            castle_chance_inland: 0.15,
+            placement: TownPlacement::Random,
+            min_town_distance: 12.0,
+            water_weight: 1.0,
+            flat_weight: 1.0,
+            fertile_weight: 1.0,
+            score_jitter: 0.5,
+            min_landmass_tiles: 100,
+        }
+    }
+}
+
+impl Default for DungeonConfig {
+
+    fn default() -> Self {
+        DungeonConfig {
+            style: DungeonStyle::Mixed,
+            width: 48,
+            height: 48,
+            min_floors: 1,
+            max_floors: 3,
+            rooms: 12,
+            room_min: 4,
+            room_max: 9,
+            cavern_fill: 0.45,
+            cavern_steps: 4,
+            chests_per_floor: 2,
+        }
+    }
+}
+
+impl Default for InteriorConfig {
+
+    fn default() -> Self {
+        InteriorConfig {
+            town_min_size: 24,
+            town_max_size: 40,
+            castle_min_size: 32,
+            castle_max_size: 48,
+            catchment_radius: 20,
+            min_buildings: 4,
+            max_buildings: 16,
+            church_above: 0.5,
+            npcs_per_building: 1.0,
+        }
+    }
+}
+
+impl Default for NameConfig {
+
+    fn default() -> Self {
+        NameConfig {
+            labels: true,
+            min_landmass_tiles: 50,
+            min_range_tiles: 15,
+            min_river_tiles: 8,
+            min_sea_tiles: 100,
+            ocean_tiles: 10000,
+        }
+    }
+}
+
+impl Default for KingdomConfig {
+
+    fn default() -> Self {
+        KingdomConfig {
+            hill_cost: 2.0,
+            mountain_cost: 8.0,
+            river_cost: 6.0,
+            sea_cost: 4.0,
+            strength_variance: 0.2,
+            tint: 0.4,
+        }
+    }
+}
+
+impl Config {
+
+    pub fn from_toml(text: &str) -> Result<Config, Error> {
+
+        let config: Config = toml::from_str(text).map_err(|e| Error::Config(e.to_string()))?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn from_toml_file(path: &Path) -> Result<Config, Error> {
+
+        Config::from_toml(&fs::read_to_string(path)?)
+    }
+
+    pub fn to_toml(&self) -> String {
+
+        toml::to_string(self).unwrap()
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+
+        if self.width < 32 || self.height < 32 {
+            return Err(Error::Config(format!(
+                "map must be at least 32x32 tiles, got {}x{}", self.width, self.height
+            )));
+        }
+
+        let t = &self.terrain;
+
+        check_range("terrain.cutoff_water", t.cutoff_water, 0.0, t.cutoff_terrain)?;
+        check_range("terrain.cutoff_terrain", t.cutoff_terrain, t.cutoff_water, t.cutoff_hill)?;
+        check_range("terrain.cutoff_hill", t.cutoff_hill, t.cutoff_terrain, HEIGHTMAP_RANGE as f32)?;
+        check_scale("terrain.noise_scale", t.noise_scale)?;
+        check_range("terrain.land_radius", t.land_radius, 0.01, 1.0)?;
+
+        let m = &self.layout;
+
+        if m.count == 0 || m.count > 50 {
+            return Err(Error::Config(format!("layout.count must be between 1 and 50, got {}", m.count)));
+        }
+        check_range("layout.size_variance", m.size_variance, 0.0, 0.9)?;
+        check_range("layout.min_separation", m.min_separation, 0.0, 1.0)?;
+        check_range("layout.mask_strength", m.mask_strength, 0.0, 1.0)?;
+
+        let e = &self.erosion;
+
+        check_range("erosion.droplets_per_tile", e.droplets_per_tile, 0.0, 100.0)?;
+        check_range("erosion.hydraulic_strength", e.hydraulic_strength, 0.0, 1.0)?;
+        check_range("erosion.deposition", e.deposition, 0.0, 1.0)?;
+        check_range("erosion.evaporation", e.evaporation, 0.0, 1.0)?;
+        check_range("erosion.inertia", e.inertia, 0.0, 1.0)?;
+        check_range("erosion.capacity", e.capacity, 0.0, f32::MAX)?;
+        check_range("erosion.thermal_strength", e.thermal_strength, 0.0, 1.0)?;
+        check_range("erosion.talus", e.talus, 0.0, HEIGHTMAP_RANGE as f32)?;
+
+        let b = &self.biomes;
+
+        check_range("biomes.forest_above", b.forest_above, b.thicker_grass_above, HEIGHTMAP_RANGE as f32)?;
+        check_range("biomes.thicker_grass_above", b.thicker_grass_above, b.thick_grass_above, b.forest_above)?;
+        check_range("biomes.thick_grass_above", b.thick_grass_above, b.flowers_above, b.thicker_grass_above)?;
+        check_range("biomes.flowers_above", b.flowers_above, 0.0, b.thick_grass_above)?;
+        check_range("biomes.desert_below", b.desert_below, 0.0, b.flowers_above)?;
+        check_scale("biomes.fine_noise_scale", b.fine_noise_scale)?;
+        check_scale("biomes.coarse_noise_scale", b.coarse_noise_scale)?;
+        check_range("biomes.swamp_above", b.swamp_above, 0.0, HEIGHTMAP_RANGE as f32)?;
+        check_scale("biomes.swamp_noise_scale", b.swamp_noise_scale)?;
+        check_range("biomes.dune_chance", b.dune_chance, 0.0, 1.0)?;
+
+        let c = &self.climate_model;
+
+        check_range("climate_model.pole_temperature", c.pole_temperature, -100.0, c.equator_temperature)?;
+        check_range("climate_model.equator_temperature", c.equator_temperature, c.pole_temperature, 100.0)?;
+        check_range("climate_model.lapse_rate", c.lapse_rate, 0.0, 10.0)?;
+        check_range("climate_model.rainfall", c.rainfall, 0.0, 1.0)?;
+        check_range("climate_model.rain_shadow", c.rain_shadow, 0.0, 1.0)?;
+        check_range("climate_model.water_reach", c.water_reach, 0.1, 1000.0)?;
+        check_scale("climate_model.noise_scale", c.noise_scale)?;
+        check_range("climate_model.temperature_noise", c.temperature_noise, 0.0, 100.0)?;
+        check_range("climate_model.moisture_noise", c.moisture_noise, 0.0, HEIGHTMAP_RANGE as f32)?;
+        check_range("climate_model.cold_below", c.cold_below, -100.0, c.hot_above)?;
+        check_range("climate_model.hot_above", c.hot_above, c.cold_below, 100.0)?;
+        check_range("climate_model.snow_below", c.snow_below, -100.0, c.cold_below)?;
+        check_range("climate_model.ice_below", c.ice_below, -100.0, c.snow_below)?;
+        check_range("climate_model.freeze_below", c.freeze_below, -100.0, c.snow_below)?;
+        check_range("climate_model.swamp_moisture", c.swamp_moisture, 0.0, HEIGHTMAP_RANGE as f32)?;
+        check_range("climate_model.swamp_lowland", c.swamp_lowland, 0.0, HEIGHTMAP_RANGE as f32)?;
+
+        let o = &self.ocean;
+
+        check_range("ocean.shallow_above", o.shallow_above, o.deep_below, t.cutoff_water)?;
+        check_range("ocean.deep_below", o.deep_below, 0.0, o.shallow_above)?;
+        check_range("ocean.reef_above", o.reef_above, 0.0, HEIGHTMAP_RANGE as f32)?;
+        check_scale("ocean.reef_noise_scale", o.reef_noise_scale)?;
+
+        let l = &self.lake;
+
+        check_range("lake.frequency", l.frequency, 0.0, 1.0)?;
+        check_range("lake.min_depth", l.min_depth, 0.0, HEIGHTMAP_RANGE as f32)?;
+        if l.max_size == 0 {
+            return Err(Error::Config(String::from("lake.max_size must be above 0")));
+        }
+
+        let r = &self.road;
+
+        if r.neighbors == 0 || r.max_bridge_length == 0 {
+            return Err(Error::Config(String::from("road.neighbors and road.max_bridge_length must be above 0")));
+        }
+        check_range("road.detour_factor", r.detour_factor, 1.0, f32::MAX)?;
+        check_range("road.road_cost", r.road_cost, 0.01, 1.0)?;
+        check_range("road.sand_cost", r.sand_cost, r.road_cost, f32::MAX)?;
+        check_range("road.snow_cost", r.snow_cost, r.road_cost, f32::MAX)?;
+        check_range("road.forest_cost", r.forest_cost, r.road_cost, f32::MAX)?;
+        check_range("road.hill_cost", r.hill_cost, r.road_cost, f32::MAX)?;
+        check_range("road.swamp_cost", r.swamp_cost, r.road_cost, f32::MAX)?;
+        check_range("road.bridge_cost", r.bridge_cost, r.road_cost, f32::MAX)?;
+
+        let c = &self.connectivity;
+
+        if let Some((x, y)) = c.start {
+            if x >= self.width || y >= self.height {
+                return Err(Error::Config(format!(
+                    "connectivity.start ({}, {}) is outside the {}x{} map", x, y, self.width, self.height
+                )));
+            }
+        }
+        if c.max_bridge_length == 0 {
+            return Err(Error::Config(String::from("connectivity.max_bridge_length must be above 0")));
+        }
+        check_range("connectivity.pass_cost", c.pass_cost, 1.0, f32::MAX)?;
+        check_range("connectivity.bridge_cost", c.bridge_cost, 1.0, f32::MAX)?;
+
+        let s = &self.sites;
+
+        if s.bridge_spacing == 0 {
+            return Err(Error::Config(String::from("sites.bridge_spacing must be above 0")));
+        }
+        if s.walkable_tiles_per_town == 0 {
+            return Err(Error::Config(String::from("sites.walkable_tiles_per_town must be above 0")));
+        }
+        check_range("sites.castle_chance_coast", s.castle_chance_coast, 0.0, 1.0)?;
+        check_range("sites.castle_chance_inland", s.castle_chance_inland, 0.0, 1.0)?;
+        check_range("sites.min_town_distance", s.min_town_distance, 2.0, f32::MAX)?;
+        check_range("sites.water_weight", s.water_weight, 0.0, 100.0)?;
+        check_range("sites.flat_weight", s.flat_weight, 0.0, 100.0)?;
+        check_range("sites.fertile_weight", s.fertile_weight, 0.0, 100.0)?;
+        check_range("sites.score_jitter", s.score_jitter, 0.0, 100.0)?;
+
+        let d = &self.dungeon;
+
+        if d.width < 16 || d.height < 16 {
+            return Err(Error::Config(format!(
+                "dungeon floors must be at least 16x16 tiles, got {}x{}", d.width, d.height
+            )));
+        }
+        if d.min_floors == 0 || d.max_floors < d.min_floors {
+            return Err(Error::Config(String::from(
+                "dungeon.min_floors must be above 0 and dungeon.max_floors at least dungeon.min_floors"
+            )));
+        }
+        if d.rooms == 0 || d.room_min < 3 || d.room_max < d.room_min || d.room_max > d.width.min(d.height) - 2 {
+            return Err(Error::Config(String::from(
+                "dungeon.rooms must be above 0 and rooms between 3 tiles and the floor size"
+            )));
+        }
+        check_range("dungeon.cavern_fill", d.cavern_fill, 0.0, 0.7)?;
+
+        let i = &self.interior;
+
+        if i.town_min_size < 16 || i.town_max_size < i.town_min_size || i.castle_min_size < 24 || i.castle_max_size < i.castle_min_size {
+            return Err(Error::Config(String::from(
+                "interior sizes must be at least 16 for towns and 24 for castles, with max sizes at least the min sizes"
+            )));
+        }
+        if i.catchment_radius == 0 || i.max_buildings < i.min_buildings {
+            return Err(Error::Config(String::from(
+                "interior.catchment_radius must be above 0 and interior.max_buildings at least interior.min_buildings"
+            )));
+        }
+        check_range("interior.church_above", i.church_above, 0.0, 1.0)?;
+        check_range("interior.npcs_per_building", i.npcs_per_building, 0.0, 10.0)?;
+
+        let n = &self.naming;
+
+        if n.min_landmass_tiles == 0 || n.min_range_tiles == 0 || n.min_river_tiles == 0 || n.min_sea_tiles == 0 {
+            return Err(Error::Config(String::from("naming.min_*_tiles must be above 0")));
+        }
+
+        let k = &self.kingdom;
+
+        check_range("kingdom.hill_cost", k.hill_cost, 1.0, f32::MAX)?;
+        check_range("kingdom.mountain_cost", k.mountain_cost, 1.0, f32::MAX)?;
+        check_range("kingdom.river_cost", k.river_cost, 1.0, f32::MAX)?;
+        check_range("kingdom.sea_cost", k.sea_cost, 1.0, f32::MAX)?;
+        check_range("kingdom.strength_variance", k.strength_variance, 0.0, 0.9)?;
+        check_range("kingdom.tint", k.tint, 0.0, 1.0)?;
+
+        Ok(())
+    }
+}
+
+fn check_range(name: &str, value: f32, min: f32, max: f32) -> Result<(), Error> {
+
+    if value.is_nan() || value < min || value > max {
+        return Err(Error::Config(format!("{} must be between {} and {}, got {}", name, min, max, value)));
+    }
+
+    Ok(())
+}
+
+fn check_scale(name: &str, value: f64) -> Result<(), Error> {
+
+    if !(value > 0.0 && value <= 1.0) {
+        return Err(Error::Config(format!("{} must be above 0 and at most 1, got {}", name, value)));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn defaults_are_valid() {
+
+        assert!(Config::default().validate().is_ok());
+        assert!(Config::from_toml(include_str!("../default_config.toml")).is_ok());
+    }
+
+    // Out of range values are rejected with the name of the setting
+    #[test]
+    fn validate_rejects_out_of_range() {
+
+        let rejects = |config: Config, name: &str| match config.validate() {
+            Err(Error::Config(message)) => assert!(message.contains(name), "\"{}\" doesn't mention {}", message, name),
+            _ => panic!("{} out of range was accepted", name),
+        };
+
+        rejects(Config { width: 16, ..Config::default() }, "32x32");
+        rejects(Config { height: 0, ..Config::default() }, "32x32");
+
+        let mut config = Config::default();
+        config.terrain.cutoff_water = config.terrain.cutoff_terrain + 1.0;
+        rejects(config, "terrain.cutoff_water");
+
+        let mut config = Config::default();
+        config.layout.count = 0;
+        rejects(config, "layout.count");
+
+        let mut config = Config::default();
+        config.sites.min_town_distance = 1.0;
+        rejects(config, "sites.min_town_distance");
+
+        let mut config = Config::default();
+        config.kingdom.tint = f32::NAN;
+        rejects(config, "kingdom.tint");
+
+        let mut config = Config::default();
+        config.biomes.fine_noise_scale = 0.0;
+        rejects(config, "biomes.fine_noise_scale");
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+
+        assert!(Config::from_toml("[sites]\nmin_town_distanse = 12.0\n").is_err());
+    }
+}
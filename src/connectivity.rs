@@ -0,0 +1,513 @@
+use rand::prelude::*; // Random
+use std::collections::{BinaryHeap, VecDeque}; // Open set of the passage search, nearest walkable start
+
+use crate::config::ConnectivityConfig;
+use crate::error::Warning;
+use crate::grid::{neighbors, offset};
+use crate::heightmap::FloodCell;
+use crate::roads::{trace_road, DIRECTIONS};
+use crate::tile::{Tilemap, TileCategory};
+
+// A town, castle or cave and the landmass it is on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SiteReach {
+    pub x: u32,
+    pub y: u32,
+    pub kind: &'static str, // "town", "castle" or "cave"
+    pub landmass: u32,
+}
+
+// Which walkable tiles can be reached from each other on foot, and which 
+// sites share a landmass with the start position
+#[derive(Clone, Debug)]
+pub struct Connectivity {
+    pub start: Option<(u32, u32)>,
+    pub sites: Vec<SiteReach>,
+    pub num_landmasses: u32,
+    pub passes: u32, // Mountain tiles carved into passes by repair
+    pub bridges: u32, // Bridges added by repair
+    pub relocated: u32, // Sites moved by repair
+    landmass_map: Vec<Option<u32>>,
+    width: u32,
+    wrap: bool,
+}
+
+impl Connectivity {
+
+    // Flood fill over walkable tiles. A start position on water or a 
+    // mountain moves to the nearest walkable tile. Without a start position 
+    // it goes on the largest landmass with a site, at its first castle, or 
+    // its first town or cave if it has no castles.
+    pub fn analyze(tilemap: &Tilemap, wrap: bool, start: Option<(u32, u32)>) -> Connectivity {
+
+        let width = tilemap.width();
+        let height = tilemap.height();
+
+        let mut landmass_map = vec![None; (width * height) as usize];
+        let mut num_landmasses = 0;
+        let mut sizes = vec![];
+
+        for x in 0..width {
+            for y in 0..height {
+
+                if landmass_map[(y * width + x) as usize].is_some() || !tilemap.get(x, y).walkable {
+                    continue;
+                }
+
+                let mut fill = vec![(x, y)];
+                let mut size = 0;
+                landmass_map[(y * width + x) as usize] = Some(num_landmasses);
+
+                while let Some((fx, fy)) = fill.pop() {
+
+                    size += 1;
+
+                    for (nx, ny) in neighbors(fx, fy, width, height, wrap) {
+
+                        let i = (ny * width + nx) as usize;
+
+                        if landmass_map[i].is_none() && tilemap.get(nx, ny).walkable {
+                            landmass_map[i] = Some(num_landmasses);
+                            fill.push((nx, ny));
+                        }
+                    }
+                }
+
+                sizes.push(size);
+                num_landmasses += 1;
+            }
+        }
+
+        let mut sites = vec![];
+
+        for x in 0..width {
+            for y in 0..height {
+
+                let name = tilemap.get(x, y).name;
+
+                let kind = if name.starts_with("castle") {
+                    "castle"
+                } else if name.starts_with("town") {
+                    "town"
+                } else if name.starts_with("cave") {
+                    "cave"
+                } else {
+                    continue;
+                };
+
+                if let Some(landmass) = landmass_map[(y * width + x) as usize] {
+                    sites.push(SiteReach { x, y, kind, landmass });
+                }
+            }
+        }
+
+        let start = start.and_then(|start| nearest_walkable(tilemap, start, wrap)).or_else(|| {
+            let rank = |kind| ["cave", "town", "castle"].iter().position(|&k| k == kind);
+            sites.iter().enumerate()
+                .max_by_key(|&(i, s)| (sizes[s.landmass as usize], rank(s.kind), std::cmp::Reverse(i)))
+                .map(|(_, s)| (s.x, s.y))
+        });
+
+        Connectivity { start, sites, num_landmasses, passes: 0, bridges: 0, relocated: 0, landmass_map, width, wrap }
+    }
+
+    pub fn landmass_at(&self, x: u32, y: u32) -> Option<u32> {
+
+        self.landmass_map[(y * self.width + x) as usize]
+    }
+
+    pub fn start_landmass(&self) -> Option<u32> {
+
+        self.start.and_then(|(x, y)| self.landmass_at(x, y))
+    }
+
+    // Sites that can't be reached on foot from the start position
+    pub fn unreachable(&self) -> Vec<SiteReach> {
+
+        let start = self.start_landmass();
+
+        self.sites.iter().filter(|s| Some(s.landmass) != start).copied().collect()
+    }
+
+    pub fn sites_on(&self, landmass: u32) -> Vec<SiteReach> {
+
+        self.sites.iter().filter(|s| s.landmass == landmass).copied().collect()
+    }
+}
+
+// Closest walkable tile to "from" by steps across the map, "from" itself 
+// when it is walkable. None on a map without walkable tiles.
+fn nearest_walkable(tilemap: &Tilemap, from: (u32, u32), wrap: bool) -> Option<(u32, u32)> {
+
+    let width = tilemap.width();
+    let height = tilemap.height();
+
+    let mut seen = vec![false; (width * height) as usize];
+    let mut queue = VecDeque::new();
+
+    seen[(from.1 * width + from.0) as usize] = true;
+    queue.push_back(from);
+
+    while let Some((x, y)) = queue.pop_front() {
+
+        if tilemap.get(x, y).walkable {
+            return Some((x, y));
+        }
+
+        for (nx, ny) in neighbors(x, y, width, height, wrap) {
+            if !seen[(ny * width + nx) as usize] {
+                seen[(ny * width + nx) as usize] = true;
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+
+    None
+}
+
+// One step of a passage from "current" in direction (dx, dy). Like roads, 
+// rivers are crossed in a straight line. Mountains can be carved through.
+fn passage_step(
+    tilemap: &Tilemap, 
+    river_map: &[Vec<bool>], 
+    current: (u32, u32), 
+    direction: (i32, i32), 
+    wrap: bool, 
+    connectivity: &ConnectivityConfig
+) -> Option<((u32, u32), f32)> {
+
+    let mut bridge_length = 0;
+    let mut cost = 0.0;
+
+    let landing = loop {
+
+        let (x, y) = offset(current.0, current.1, direction, bridge_length + 1, tilemap.width(), tilemap.height(), wrap)?;
+
+        if !river_map[x as usize][y as usize] {
+            break (x, y);
+        }
+
+        let tile = tilemap.get(x, y);
+
+        if tile.bridge {
+            if (tile.name == "bridge_up_down") != (direction.1 != 0) {
+                return None;
+            }
+            cost += 1.0;
+        } else if tile.cat == TileCategory::Water {
+            cost += connectivity.bridge_cost;
+        } else {
+            return None;
+        }
+
+        bridge_length += 1;
+
+        if bridge_length > connectivity.max_bridge_length as i32 {
+            return None;
+        }
+    };
+
+    let tile = tilemap.get(landing.0, landing.1);
+
+    if tile.walkable {
+        Some((landing, cost + 1.0))
+    } else if tile.name.starts_with("mountain") {
+        Some((landing, cost + connectivity.pass_cost))
+    } else {
+        None
+    }
+}
+
+// Cheapest way from a site to the start landmass, carving passes and 
+// building bridges where needed
+fn find_passage(
+    tilemap: &Tilemap, 
+    river_map: &[Vec<bool>], 
+    report: &Connectivity, 
+    from: (u32, u32), 
+    connectivity: &ConnectivityConfig
+) -> Option<Vec<(u32, u32)>> {
+
+    let width = tilemap.width();
+    let height = tilemap.height();
+    let target = report.start_landmass()?;
+
+    let index = |(x, y): (u32, u32)| (y * width + x) as usize;
+
+    let mut cost = vec![f32::INFINITY; (width * tilemap.height()) as usize];
+    let mut came_from = vec![usize::MAX; (width * tilemap.height()) as usize];
+    let mut open = BinaryHeap::new();
+
+    cost[index(from)] = 0.0;
+    open.push(FloodCell { h: 0.0, x: from.0, y: from.1 });
+
+    while let Some(cell) = open.pop() {
+
+        let current = (cell.x, cell.y);
+
+        if cell.h > cost[index(current)] {
+            continue;
+        }
+
+        if report.landmass_at(current.0, current.1) == Some(target) {
+            return Some(trace_road(&came_from, current, width, height, report.wrap));
+        }
+
+        for &direction in DIRECTIONS.iter() {
+
+            if let Some((next, step)) = passage_step(tilemap, river_map, current, direction, report.wrap, connectivity) {
+
+                if cost[index(current)] + step < cost[index(next)] {
+                    cost[index(next)] = cost[index(current)] + step;
+                    came_from[index(next)] = index(current);
+                    open.push(FloodCell { h: cost[index(next)], x: next.0, y: next.1 });
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// Move a site somewhere on the start landmass
+fn relocate_site<R: Rng>(
+    tilemap: &mut Tilemap, 
+    report: &Connectivity, 
+    site: SiteReach, 
+    rng: &mut R
+) -> bool {
+
+    let width = tilemap.width();
+    let height = tilemap.height();
+
+    let target = match report.start_landmass() {
+        Some(landmass) => landmass,
+        None => return false,
+    };
+
+    let mut candidates = vec![];
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let tile = tilemap.get(x, y);
+
+            let valid = if site.kind == "cave" {
+
+                // Caves go into a mountain side facing the start landmass, 
+                // or a hill on it like the caves place_caves puts in hills
+                (tile.name.starts_with("mountain") &&
+                    neighbors(x, y, width, height, report.wrap).iter().any(|n| report.landmass_at(n.0, n.1) == Some(target)))
+                || (tile.name.starts_with("hill") && report.landmass_at(x, y) == Some(target))
+
+            } else {
+
+                report.landmass_at(x, y) == Some(target) &&
+                    (tile.cat == TileCategory::Grass || tile.cat == TileCategory::Sand || tile.cat == TileCategory::Snow) &&
+                    !(tile.dwelling || tile.bridge || tile.name.starts_with("cave") || tile.name.starts_with("road")) &&
+                    !neighbors(x, y, width, height, report.wrap).iter().any(|n| tilemap.get(n.0, n.1).dwelling)
+            };
+
+            if valid {
+                candidates.push((x, y));
+            }
+        }
+    }
+
+    if candidates.is_empty() {
+        return false;
+    }
+
+    let (new_x, new_y) = candidates[rng.gen_range(0, candidates.len())];
+
+    let old_ground = tilemap.get(site.x, site.y).cat.ground();
+    let new_ground = tilemap.get(new_x, new_y).cat.ground();
+
+    // Caves go back to a mountain only when one walkable neighbor at most 
+    // leads into them, so no path runs through. Caves in hills or passes 
+    // go back to a hill.
+    let ways_in = neighbors(site.x, site.y, width, height, report.wrap).iter()
+        .filter(|n| tilemap.get(n.0, n.1).walkable)
+        .count();
+
+    let old_name = match (site.kind, old_ground) {
+        ("cave", ground) if ways_in > 1 => ["hill_", ground].concat(),
+        ("cave", ground) => ["mountain_", ground].concat(),
+        (_, "grass") => String::from("grass"),
+        (_, ground) => [ground, "_0000"].concat(),
+    };
+
+    tilemap.set_by_name(site.x, site.y, &old_name);
+    tilemap.set_by_name(new_x, new_y, &[site.kind, "_", new_ground].concat());
+
+    true
+}
+
+// Make every town, castle and cave reachable on foot from the start 
+// position, carving passes through mountains and bridging rivers where that 
+// is possible, and otherwise moving the site. Warns about every site that 
+// is still out of reach.
+pub fn repair_connectivity<R: Rng>(
+    tilemap: &mut Tilemap, 
+    river_map: &[Vec<bool>], 
+    wrap: bool, 
+    connectivity: &ConnectivityConfig, 
+    rng: &mut R
+) -> (Connectivity, Vec<Warning>) {
+
+    let mut passes = 0;
+    let mut bridges = 0;
+    let mut relocated = 0;
+    let mut given_up = vec![];
+
+    // Moving castles around must not move the default start with them
+    let start = Connectivity::analyze(tilemap, wrap, connectivity.start).start;
+
+    loop {
+
+        let report = Connectivity::analyze(tilemap, wrap, start);
+
+        let site = match report.unreachable().into_iter().find(|s| !given_up.contains(&(s.x, s.y))) {
+            Some(site) => site,
+            None => {
+                let warnings = report.unreachable().iter()
+                    .map(|s| Warning::Unreachable { kind: s.kind, x: s.x, y: s.y })
+                    .collect();
+                return (Connectivity { passes, bridges, relocated, ..report }, warnings);
+            }
+        };
+
+        if let Some(path) = find_passage(tilemap, river_map, &report, (site.x, site.y), connectivity) {
+
+            for (n, &(x, y)) in path.iter().enumerate() {
+
+                let tile = tilemap.get(x, y);
+
+                if river_map[x as usize][y as usize] && !tile.bridge {
+
+                    let bridge = if path[n - 1].0 == x { "bridge_up_down" } else { "bridge_left_right" };
+                    tilemap.set_by_name(x, y, bridge);
+                    bridges += 1;
+
+                } else if tile.name.starts_with("mountain") {
+
+                    tilemap.set_by_name(x, y, &["hill_", tile.cat.ground()].concat());
+                    passes += 1;
+                }
+            }
+
+        } else if connectivity.relocate && relocate_site(tilemap, &report, site, rng) {
+            relocated += 1;
+        } else {
+            given_up.push((site.x, site.y));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::tile::Tilelist;
+    use rand_chacha::ChaCha8Rng;
+    use std::sync::Arc;
+
+    // From left to right: a town, a mountain ridge, a river, the castle, 
+    // the sea and an island with a town of its own
+    fn split_map() -> (Tilemap, Vec<Vec<bool>>) {
+
+        let mut tilemap = Tilemap::new(16, 7, "grass", &Arc::new(Tilelist::old_school()));
+        let mut river_map = vec![vec![false; 7]; 16];
+
+        for y in 0..7 {
+            tilemap.set_by_name(5, y, "mountain_grass");
+            tilemap.set_by_name(6, y, "water_0000");
+            tilemap.set_by_name(13, y, "deep_water");
+            river_map[6][y as usize] = true;
+        }
+
+        tilemap.set_by_name(2, 3, "town_grass");
+        tilemap.set_by_name(9, 3, "castle_grass");
+        tilemap.set_by_name(15, 3, "town_grass");
+
+        (tilemap, river_map)
+    }
+
+    #[test]
+    fn every_site_reachable_after_repair() {
+
+        let (mut tilemap, river_map) = split_map();
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+
+        assert_eq!(Connectivity::analyze(&tilemap, false, None).unreachable().len(), 2);
+
+        let (report, warnings) = repair_connectivity(&mut tilemap, &river_map, false, &ConnectivityConfig::default(), &mut rng);
+
+        assert_eq!(report.start, Some((9, 3)));
+        assert!(report.unreachable().is_empty());
+        assert!(warnings.is_empty());
+        assert!(report.passes >= 1 && report.bridges >= 1);
+        assert_eq!(report.relocated, 1);
+        assert!(!tilemap.get(15, 3).dwelling);
+
+        // Checked again from scratch, not just by the report repair returned
+        assert!(Connectivity::analyze(&tilemap, false, report.start).unreachable().is_empty());
+    }
+
+    #[test]
+    fn sites_left_out_of_reach_warn() {
+
+        let (mut tilemap, river_map) = split_map();
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let config = ConnectivityConfig { relocate: false, ..ConnectivityConfig::default() };
+
+        let (report, warnings) = repair_connectivity(&mut tilemap, &river_map, false, &config, &mut rng);
+
+        assert_eq!(report.unreachable().len(), 1);
+        assert_eq!(warnings, vec![Warning::Unreachable { kind: "town", x: 15, y: 3 }]);
+    }
+
+    #[test]
+    fn relocated_hill_cave_leaves_a_hill() {
+
+        // Grass with the castle and one hill on the left, the sea, and hills 
+        // with a cave in the middle of them on the right
+        let mut tilemap = Tilemap::new(12, 5, "grass", &Arc::new(Tilelist::old_school()));
+        let river_map = vec![vec![false; 5]; 12];
+
+        for y in 0..5 {
+            tilemap.set_by_name(5, y, "deep_water");
+            for x in 6..12 {
+                tilemap.set_by_name(x, y, "hill_grass");
+            }
+        }
+
+        tilemap.set_by_name(0, 0, "hill_grass");
+        tilemap.set_by_name(2, 2, "castle_grass");
+        tilemap.set_by_name(9, 2, "cave_grass");
+
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let config = ConnectivityConfig { start: Some((2, 2)), ..ConnectivityConfig::default() };
+
+        let (report, warnings) = repair_connectivity(&mut tilemap, &river_map, false, &config, &mut rng);
+
+        assert!(warnings.is_empty());
+        assert_eq!(report.relocated, 1);
+        assert_eq!(tilemap.get(0, 0).name, "cave_grass");
+        assert_eq!(tilemap.get(9, 2).name, "hill_grass");
+    }
+
+    #[test]
+    fn start_on_mountain_moves_to_land() {
+
+        let (tilemap, _) = split_map();
+
+        // On the ridge, with the river on one side and the town's land on 
+        // the other
+        let report = Connectivity::analyze(&tilemap, false, Some((5, 2)));
+
+        assert_eq!(report.start, Some((4, 2)));
+        assert_eq!(report.start_landmass(), report.landmass_at(2, 3));
+        assert_eq!(report.unreachable().len(), 2);
+    }
+}
@@ -0,0 +1,394 @@
+use rand::prelude::*; // Random
+use std::collections::VecDeque; // Distances from the stairs, nearest first
+use std::sync::Arc; // Tile list shared with the overworld
+
+use crate::config::{DungeonConfig, DungeonStyle};
+use crate::grid::neighbors;
+use crate::tile::{Tilemap, Tilelist};
+
+// One level of a dungeon. Stairs up on the first floor lead back out to 
+// the cave, the last floor has a boss instead of stairs down.
+pub struct Floor {
+    pub tilemap: Tilemap,
+    pub up: (u32, u32),
+    pub down: Option<(u32, u32)>,
+    pub boss: Option<(u32, u32)>,
+}
+
+// Dungeon behind the cave at "cave" on the overworld
+pub struct Dungeon {
+    pub cave: (u32, u32),
+    pub floors: Vec<Floor>,
+}
+
+impl Dungeon {
+
+    // Where the player arrives when entering the cave
+    pub fn entry(&self) -> (u32, u32) {
+
+        self.floors[0].up
+    }
+}
+
+// Left, top, width and height of a room
+type Room = (u32, u32, u32, u32);
+
+fn center(room: &Room) -> (u32, u32) {
+
+    (room.0 + room.2 / 2, room.1 + room.3 / 2)
+}
+
+// Rooms dropped at random, leaving out those that overlap or touch one 
+// already there. Each room gets an L-shaped corridor to the one before, so 
+// every room can be reached.
+fn room_layout<R: Rng>(width: u32, height: u32, config: &DungeonConfig, rng: &mut R) -> (Vec<Vec<bool>>, Vec<Room>) {
+
+    let mut open = vec![vec![false; height as usize]; width as usize];
+    let mut rooms: Vec<Room> = vec![];
+
+    for _ in 0..config.rooms {
+
+        let w = rng.gen_range(config.room_min, config.room_max + 1);
+        let h = rng.gen_range(config.room_min, config.room_max + 1);
+        let x = rng.gen_range(1, width - w);
+        let y = rng.gen_range(1, height - h);
+
+        let overlaps = rooms.iter().any(|r| {
+            x <= r.0 + r.2 && r.0 <= x + w && y <= r.1 + r.3 && r.1 <= y + h
+        });
+
+        if overlaps {
+            continue;
+        }
+
+        for column in open.iter_mut().skip(x as usize).take(w as usize) {
+            for cell in column.iter_mut().skip(y as usize).take(h as usize) {
+                *cell = true;
+            }
+        }
+
+        let room = (x, y, w, h);
+
+        if let Some(last) = rooms.last() {
+
+            let (ax, ay) = center(last);
+            let (bx, by) = center(&room);
+
+            // Bend either after the horizontal or after the vertical leg
+            let corner = if rng.gen::<bool>() { (bx, ay) } else { (ax, by) };
+
+            for &(from, to) in [((ax, ay), corner), (corner, (bx, by))].iter() {
+                for cx in from.0.min(to.0)..=from.0.max(to.0) {
+                    for cy in from.1.min(to.1)..=from.1.max(to.1) {
+                        open[cx as usize][cy as usize] = true;
+                    }
+                }
+            }
+        }
+
+        rooms.push(room);
+    }
+
+    (open, rooms)
+}
+
+// Random walls smoothed with cellular automata into caverns. Only the 
+// largest cavern is kept. None if it covers less than a quarter of the 
+// floor, the caller tries again.
+fn cavern_layout<R: Rng>(width: u32, height: u32, config: &DungeonConfig, rng: &mut R) -> Option<Vec<Vec<bool>>> {
+
+    let mut open = vec![vec![false; height as usize]; width as usize];
+
+    for x in 1..width - 1 {
+        for y in 1..height - 1 {
+            open[x as usize][y as usize] = rng.gen::<f32>() >= config.cavern_fill;
+        }
+    }
+
+    for _ in 0..config.cavern_steps {
+
+        let mut next = vec![vec![false; height as usize]; width as usize];
+
+        for x in 1..width - 1 {
+            for y in 1..height - 1 {
+
+                // Walls in the 3x3 block around the tile, itself included
+                let mut walls = 0;
+
+                for nx in x - 1..=x + 1 {
+                    for ny in y - 1..=y + 1 {
+                        if !open[nx as usize][ny as usize] {
+                            walls += 1;
+                        }
+                    }
+                }
+
+                next[x as usize][y as usize] = walls < 5;
+            }
+        }
+
+        open = next;
+    }
+
+    let mut region = vec![vec![0; height as usize]; width as usize];
+    let mut sizes = vec![0];
+
+    for x in 0..width {
+        for y in 0..height {
+
+            if !open[x as usize][y as usize] || region[x as usize][y as usize] != 0 {
+                continue;
+            }
+
+            let id = sizes.len();
+            let mut size = 0;
+            let mut queue = VecDeque::new();
+
+            region[x as usize][y as usize] = id;
+            queue.push_back((x, y));
+
+            while let Some((cx, cy)) = queue.pop_front() {
+
+                size += 1;
+
+                for (nx, ny) in neighbors(cx, cy, width, height, false) {
+                    if open[nx as usize][ny as usize] && region[nx as usize][ny as usize] == 0 {
+                        region[nx as usize][ny as usize] = id;
+                        queue.push_back((nx, ny));
+                    }
+                }
+            }
+
+            sizes.push(size);
+        }
+    }
+
+    let (largest, size) = sizes.iter().enumerate().max_by_key(|(_, s)| **s)?;
+
+    if *size < width * height / 4 {
+        return None;
+    }
+
+    for x in 0..width as usize {
+        for y in 0..height as usize {
+            open[x][y] = region[x][y] == largest;
+        }
+    }
+
+    Some(open)
+}
+
+// Steps from "from" to every open tile, u32::MAX for walls
+fn distances(open: &[Vec<bool>], width: u32, height: u32, from: (u32, u32)) -> Vec<Vec<u32>> {
+
+    let mut dist = vec![vec![u32::MAX; height as usize]; width as usize];
+    let mut queue = VecDeque::new();
+
+    dist[from.0 as usize][from.1 as usize] = 0;
+    queue.push_back(from);
+
+    while let Some((x, y)) = queue.pop_front() {
+
+        let d = dist[x as usize][y as usize];
+
+        for (nx, ny) in neighbors(x, y, width, height, false) {
+            if open[nx as usize][ny as usize] && dist[nx as usize][ny as usize] == u32::MAX {
+                dist[nx as usize][ny as usize] = d + 1;
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+
+    dist
+}
+
+// The stairs up go in the first room or anywhere in a cavern. The stairs 
+// down, or the boss on the last floor, go as far from them as possible: in 
+// the middle of the farthest room, or the farthest tile of a cavern. Chests 
+// go in dead ends first, then anywhere else.
+fn build_floor<R: Rng>(tilelist: &Arc<Tilelist>, config: &DungeonConfig, last: bool, rng: &mut R) -> Floor {
+
+    let width = config.width;
+    let height = config.height;
+
+    let caverns = match config.style {
+        DungeonStyle::Rooms => false,
+        DungeonStyle::Caverns => true,
+        DungeonStyle::Mixed => rng.gen::<bool>(),
+    };
+
+    let mut layout = None;
+
+    if caverns {
+        for _ in 0..10 {
+            layout = cavern_layout(width, height, config, rng).map(|open| (open, vec![]));
+            if layout.is_some() {
+                break;
+            }
+        }
+    }
+
+    // Rooms are also the fallback for caverns that keep coming out too small
+    let (open, rooms) = layout.unwrap_or_else(|| room_layout(width, height, config, rng));
+
+    let mut tiles = vec![];
+
+    for x in 0..width {
+        for y in 0..height {
+            if open[x as usize][y as usize] {
+                tiles.push((x, y));
+            }
+        }
+    }
+
+    let up = match rooms.first() {
+        Some(room) => center(room),
+        None => *tiles.choose(rng).unwrap(),
+    };
+
+    let dist = distances(&open, width, height, up);
+
+    let mut goals: Vec<(u32, u32)> = rooms.iter().map(center).filter(|c| *c != up).collect();
+
+    if goals.is_empty() {
+        goals = tiles.iter().copied().filter(|t| *t != up).collect();
+    }
+
+    let goal = *goals.iter().max_by_key(|(x, y)| dist[*x as usize][*y as usize]).unwrap();
+
+    let mut tilemap = Tilemap::new(width, height, "dungeon_wall", tilelist);
+
+    for &(x, y) in tiles.iter() {
+        tilemap.set_by_name(x, y, "dungeon_floor");
+    }
+
+    tilemap.set_by_name(up.0, up.1, "stairs_up");
+    tilemap.set_by_name(goal.0, goal.1, if last { "boss" } else { "stairs_down" });
+
+    let mut dead_ends = vec![];
+    let mut others = vec![];
+
+    for &(x, y) in tiles.iter() {
+
+        if (x, y) == up || (x, y) == goal {
+            continue;
+        }
+
+        let exits = neighbors(x, y, width, height, false).iter().filter(|(nx, ny)| open[*nx as usize][*ny as usize]).count();
+
+        if exits == 1 {
+            dead_ends.push((x, y));
+        } else {
+            others.push((x, y));
+        }
+    }
+
+    dead_ends.shuffle(rng);
+    others.shuffle(rng);
+
+    for &(x, y) in dead_ends.iter().chain(others.iter()).take(config.chests_per_floor as usize) {
+        tilemap.set_by_name(x, y, "chest");
+    }
+
+    Floor {
+        tilemap,
+        up,
+        down: if last { None } else { Some(goal) },
+        boss: if last { Some(goal) } else { None },
+    }
+}
+
+// A dungeon for every cave on the overworld, in reading order
+pub fn build_dungeons<R: Rng>(tilemap: &Tilemap, config: &DungeonConfig, rng: &mut R) -> Vec<Dungeon> {
+
+    let mut dungeons = vec![];
+
+    for y in 0..tilemap.height() {
+        for x in 0..tilemap.width() {
+
+            if !tilemap.get(x, y).name.starts_with("cave_") {
+                continue;
+            }
+
+            let count = rng.gen_range(config.min_floors, config.max_floors + 1);
+
+            let floors = (0..count).map(|i| build_floor(tilemap.tilelist(), config, i == count - 1, rng)).collect();
+
+            dungeons.push(Dungeon { cave: (x, y), floors });
+        }
+    }
+
+    dungeons
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    // Tiles reachable on foot from "from"
+    fn reachable(tilemap: &Tilemap, from: (u32, u32)) -> Vec<Vec<bool>> {
+
+        let (width, height) = (tilemap.width(), tilemap.height());
+        let mut seen = vec![vec![false; height as usize]; width as usize];
+        let mut queue = VecDeque::new();
+
+        seen[from.0 as usize][from.1 as usize] = true;
+        queue.push_back(from);
+
+        while let Some((x, y)) = queue.pop_front() {
+            for (nx, ny) in neighbors(x, y, width, height, false) {
+                if !seen[nx as usize][ny as usize] && tilemap.get(nx, ny).walkable {
+                    seen[nx as usize][ny as usize] = true;
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+
+        seen
+    }
+
+    // Down the stairs of every floor in turn, from the cave to the boss
+    #[test]
+    fn stairs_lead_from_entry_to_boss() {
+
+        let mut overworld = Tilemap::new(8, 8, "grass", &Arc::new(Tilelist::old_school()));
+
+        for &(x, y) in [(1, 1), (4, 2), (6, 6), (2, 5)].iter() {
+            overworld.set_by_name(x, y, "cave_grass");
+        }
+
+        let config = DungeonConfig { min_floors: 2, max_floors: 4, ..DungeonConfig::default() };
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+
+        let dungeons = build_dungeons(&overworld, &config, &mut rng);
+
+        assert_eq!(dungeons.len(), 4);
+
+        for dungeon in dungeons.iter() {
+
+            let last = dungeon.floors.len() - 1;
+
+            for (i, floor) in dungeon.floors.iter().enumerate() {
+
+                assert_eq!(floor.tilemap.get(floor.up.0, floor.up.1).name, "stairs_up");
+
+                let goal = if i == last {
+                    assert!(floor.down.is_none());
+                    floor.boss.unwrap()
+                } else {
+                    assert!(floor.boss.is_none());
+                    floor.down.unwrap()
+                };
+
+                let name = if i == last { "boss" } else { "stairs_down" };
+                assert_eq!(floor.tilemap.get(goal.0, goal.1).name, name);
+
+                let seen = reachable(&floor.tilemap, floor.up);
+                assert!(seen[goal.0 as usize][goal.1 as usize], "floor {} of the dungeon at {:?} is cut off", i, dungeon.cave);
+            }
+        }
+    }
+}
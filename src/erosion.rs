@@ -0,0 +1,258 @@
+use terr::heightmap::Heightmap;
+
+use rand::prelude::*; // Random
+
+use crate::config::{TerrainConfig, ErosionConfig};
+use crate::grid::neighbors;
+
+// Cell a point is in. On a wrapping map rem_euclid of a tiny negative 
+// position rounds to exactly the width or height, which wraps back to 0.
+fn cell(x: f32, y: f32, width: u32, height: u32) -> (u32, u32) {
+
+    (x as u32 % width, y as u32 % height)
+}
+
+// Height and downhill slope at a point between cells, interpolated from the 
+// 4 cells around it
+fn height_and_gradient(heightmap: &Heightmap<f32>, x: f32, y: f32, width: u32, height: u32) -> (f32, f32, f32) {
+
+    let (cx, cy) = cell(x, y, width, height);
+    let (ox, oy) = (x.fract(), y.fract());
+
+    let h_nw = heightmap.get(cx, cy);
+    let h_ne = heightmap.get((cx + 1) % width, cy);
+    let h_sw = heightmap.get(cx, (cy + 1) % height);
+    let h_se = heightmap.get((cx + 1) % width, (cy + 1) % height);
+
+    let gradient_x = (h_ne - h_nw) * (1.0 - oy) + (h_se - h_sw) * oy;
+    let gradient_y = (h_sw - h_nw) * (1.0 - ox) + (h_se - h_ne) * ox;
+
+    let h = h_nw * (1.0 - ox) * (1.0 - oy) + h_ne * ox * (1.0 - oy) + h_sw * (1.0 - ox) * oy + h_se * ox * oy;
+
+    (h, gradient_x, gradient_y)
+}
+
+// Add "amount" to the 4 cells around a point, weighted by how close they are. 
+// Negative amounts erode.
+fn spread(heightmap: &mut Heightmap<f32>, x: f32, y: f32, width: u32, height: u32, amount: f32) {
+
+    let (cx, cy) = cell(x, y, width, height);
+    let (ox, oy) = (x.fract(), y.fract());
+
+    let cells = [
+        (cx, cy, (1.0 - ox) * (1.0 - oy)),
+        ((cx + 1) % width, cy, ox * (1.0 - oy)),
+        (cx, (cy + 1) % height, (1.0 - ox) * oy),
+        ((cx + 1) % width, (cy + 1) % height, ox * oy),
+    ];
+
+    for &(nx, ny, weight) in cells.iter() {
+        heightmap.set(nx, ny, heightmap.get(nx, ny) + amount * weight);
+    }
+}
+
+// Particle based hydraulic erosion. Raindrops run downhill, picking up 
+// sediment where they speed up and dropping it where they slow down, which 
+// carves valleys and leaves ridges between them.
+pub fn hydraulic_erosion<R: Rng>(
+    heightmap: &mut Heightmap<f32>, 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    terrain: &TerrainConfig, 
+    erosion: &ErosionConfig, 
+    rng: &mut R
+) {
+
+    let gravity = 4.0;
+    let min_capacity = 0.01;
+
+    let num_droplets = (width as f32 * height as f32 * erosion.droplets_per_tile) as u32;
+
+    // Without wrapping the last row and column have no cell past them to 
+    // interpolate with
+    let (max_x, max_y) = if wrap { (width as f32, height as f32) } else { ((width - 1) as f32, (height - 1) as f32) };
+
+    for _ in 0..num_droplets {
+
+        let mut x = rng.gen::<f32>() * max_x;
+        let mut y = rng.gen::<f32>() * max_y;
+        let mut dir_x = 0.0;
+        let mut dir_y = 0.0;
+        let mut speed = 1.0;
+        let mut water = 1.0;
+        let mut sediment = 0.0;
+
+        for _ in 0..erosion.droplet_lifetime {
+
+            let (h, gradient_x, gradient_y) = height_and_gradient(heightmap, x, y, width, height);
+
+            // Rain on the sea doesn't erode anything
+            if h < terrain.cutoff_water {
+                break;
+            }
+
+            // Keep some of the old direction, turn the rest downhill
+            dir_x = dir_x * erosion.inertia - gradient_x * (1.0 - erosion.inertia);
+            dir_y = dir_y * erosion.inertia - gradient_y * (1.0 - erosion.inertia);
+
+            let length = (dir_x * dir_x + dir_y * dir_y).sqrt();
+
+            if length == 0.0 {
+                break;
+            }
+
+            dir_x /= length;
+            dir_y /= length;
+
+            let (old_x, old_y) = (x, y);
+
+            x += dir_x;
+            y += dir_y;
+
+            if wrap {
+                x = x.rem_euclid(width as f32);
+                y = y.rem_euclid(height as f32);
+            } else if x < 0.0 || y < 0.0 || x >= max_x || y >= max_y {
+                break;
+            }
+
+            let delta = height_and_gradient(heightmap, x, y, width, height).0 - h;
+
+            // Faster and fuller drops going steeper downhill carry more
+            let capacity = (-delta * speed * water * erosion.capacity).max(min_capacity);
+
+            if sediment > capacity || delta > 0.0 {
+
+                // Uphill the drop fills the pit behind it, otherwise it 
+                // drops part of what it can't carry
+                let amount = if delta > 0.0 { delta.min(sediment) } else { (sediment - capacity) * erosion.deposition };
+
+                sediment -= amount;
+                spread(heightmap, old_x, old_y, width, height, amount);
+
+            } else {
+
+                // Never dig deeper than the step down, or holes form
+                let amount = ((capacity - sediment) * erosion.hydraulic_strength).min(-delta);
+
+                sediment += amount;
+                spread(heightmap, old_x, old_y, width, height, -amount);
+            }
+
+            speed = (speed * speed - delta * gravity).max(0.0).sqrt();
+            water *= 1.0 - erosion.evaporation;
+        }
+    }
+}
+
+// Thermal erosion. Slopes steeper than the talus angle crumble, moving 
+// material to the lowest neighbor, which softens cliffs and spikes.
+pub fn thermal_erosion(
+    heightmap: &mut Heightmap<f32>, 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    erosion: &ErosionConfig
+) {
+
+    for _ in 0..erosion.thermal_iterations {
+        for x in 0..width {
+            for y in 0..height {
+
+                let h = heightmap.get(x, y);
+
+                let lowest = neighbors(x, y, width, height, wrap).into_iter()
+                    .min_by(|a, b| heightmap.get(a.0, a.1).partial_cmp(&heightmap.get(b.0, b.1)).unwrap())
+                    .unwrap();
+
+                let diff = h - heightmap.get(lowest.0, lowest.1);
+
+                if diff > erosion.talus {
+
+                    let amount = (diff - erosion.talus) / 2.0 * erosion.thermal_strength;
+
+                    heightmap.set(x, y, h - amount);
+                    heightmap.set(lowest.0, lowest.1, heightmap.get(lowest.0, lowest.1) + amount);
+                }
+            }
+        }
+    }
+}
+
+// Thermal then hydraulic erosion. The coastline stays where it was: land 
+// is never worn down below the water cutoff and the sea is left alone.
+pub fn erode<R: Rng>(
+    heightmap: &mut Heightmap<f32>, 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    terrain: &TerrainConfig, 
+    erosion: &ErosionConfig, 
+    rng: &mut R
+) {
+
+    let mut original = vec![0.0; (width * height) as usize];
+
+    for x in 0..width {
+        for y in 0..height {
+            original[(y * width + x) as usize] = heightmap.get(x, y);
+        }
+    }
+
+    thermal_erosion(heightmap, width, height, wrap, erosion);
+    hydraulic_erosion(heightmap, width, height, wrap, terrain, erosion, rng);
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let orig_val = original[(y * width + x) as usize];
+
+            let new_val = if orig_val < terrain.cutoff_water {
+                orig_val
+            } else {
+                heightmap.get(x, y).max(terrain.cutoff_water)
+            };
+
+            heightmap.set(x, y, new_val);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn total(heightmap: &Heightmap<f32>, width: u32, height: u32) -> f64 {
+
+        (0..width).flat_map(|x| (0..height).map(move |y| (x, y))).map(|(x, y)| heightmap.get(x, y) as f64).sum()
+    }
+
+    // Erosion only moves material downhill or washes it away, so over land 
+    // well above the sea the total height can only go down. The tolerance 
+    // covers f32 rounding over every droplet step.
+    #[test]
+    fn erosion_never_adds_height() {
+
+        let (width, height) = (32, 32);
+        let mut heightmap = Heightmap::new_flat((width, height), (0.0, 0.0));
+
+        for x in 0..width {
+            for y in 0..height {
+                heightmap.set(x, y, 120.0 + 40.0 * (x as f32 * 0.4).sin() * (y as f32 * 0.3).cos());
+            }
+        }
+
+        let before = total(&heightmap, width, height);
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+
+        erode(&mut heightmap, width, height, false, &TerrainConfig::default(), &ErosionConfig::default(), &mut rng);
+
+        let after = total(&heightmap, width, height);
+
+        assert!(after <= before + 1.0, "total height went from {} to {}", before, after);
+    }
+}
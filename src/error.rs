@@ -0,0 +1,70 @@
+use std::fmt;
+use std::io;
+
+// Everything that can go wrong while generating, loading or saving a map
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Image(image::ImageError),
+    Config(String),
+    Heightmap(String),
+    MapFile(String),
+    Tileset(String),
+}
+
+impl fmt::Display for Error {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Image(e) => write!(f, "{}", e),
+            Error::Config(msg) => write!(f, "Invalid config: {}", msg),
+            Error::Heightmap(msg) => write!(f, "Heightmap generation failed: {}", msg),
+            Error::MapFile(msg) => write!(f, "Invalid map file: {}", msg),
+            Error::Tileset(msg) => write!(f, "Invalid tileset: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// Things that didn't go as asked but still left a usable map
+#[derive(Clone, Debug, PartialEq)]
+pub enum Warning {
+    FewerBridges { requested: usize, placed: usize, candidates: usize },
+    NoRoom { what: &'static str }, // Nowhere on the map qualified, so the stage placed nothing
+    Unreachable { kind: &'static str, x: u32, y: u32 }, // A site repair couldn't connect or move
+    Townless { tiles: u32 }, // A landmass large enough for a town had no spot far enough from the others
+}
+
+impl fmt::Display for Warning {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::FewerBridges { requested, placed, candidates } => write!(
+                f, "placed {} of {} requested bridges, {} river tiles could take one", placed, requested, candidates
+            ),
+            Warning::NoRoom { what } => write!(f, "no room for {} anywhere on the map, none were placed", what),
+            Warning::Unreachable { kind, x, y } => write!(
+                f, "{} at ({}, {}) can't be reached on foot and couldn't be connected or moved", kind, x, y
+            ),
+            Warning::Townless { tiles } => write!(
+                f, "a landmass with {} walkable tiles got no town, no spot on it is min_town_distance from the others", tiles
+            ),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<image::ImageError> for Error {
+
+    fn from(e: image::ImageError) -> Self {
+        Error::Image(e)
+    }
+}
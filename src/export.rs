@@ -0,0 +1,328 @@
+use serde_json::json; // Writing Tiled JSON
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{TILE_SIZE, TILES_WIDE_SPRITE_SHEET};
+use crate::error::Error;
+use crate::render::check_sheet;
+use crate::tile::{Tile, Tilemap, Tilelist};
+
+// Comma separated tile names, one line per row
+pub fn export_csv(tilemap: &Tilemap, path: &Path) -> Result<(), Error> {
+
+    let mut out = String::new();
+
+    for y in 0..tilemap.height() {
+
+        let row: Vec<String> = (0..tilemap.width()).map(|x| tilemap.get(x, y).name.to_string()).collect();
+
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+
+    fs::write(path, out)?;
+
+    Ok(())
+}
+
+// Tiled map editor XML format (.tmx)
+pub fn export_tmx(tilemap: &Tilemap, tilelist: &Tilelist, tileset: &Path, path: &Path) -> Result<(), Error> {
+
+    let sheet = SpriteSheet::new(tileset, tilelist, path)?;
+    let width = tilemap.width();
+    let height = tilemap.height();
+
+    let mut out = String::new();
+
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!(
+        "<map version=\"1.4\" orientation=\"orthogonal\" renderorder=\"right-down\" width=\"{}\" height=\"{}\" \
+         tilewidth=\"{}\" tileheight=\"{}\" infinite=\"0\" nextlayerid=\"3\" nextobjectid=\"{}\">\n",
+        width, height, TILE_SIZE, TILE_SIZE, site_objects(tilemap).len() + 1
+    ));
+
+    // Tileset with custom properties for every tile
+    out.push_str(&format!(
+        " <tileset firstgid=\"1\" name=\"{}\" tilewidth=\"{}\" tileheight=\"{}\" tilecount=\"{}\" columns=\"{}\">\n",
+        sheet.name, TILE_SIZE, TILE_SIZE, sheet.tile_count, sheet.columns
+    ));
+    out.push_str(&format!(
+        "  <image source=\"{}\" width=\"{}\" height=\"{}\"/>\n", sheet.source, sheet.width, sheet.height
+    ));
+    for tile in tilelist.tiles() {
+        out.push_str(&format!("  <tile id=\"{}\">\n   <properties>\n", tile.id));
+        out.push_str(&format!("    <property name=\"name\" value=\"{}\"/>\n", tile.name));
+        out.push_str(&format!("    <property name=\"cat\" value=\"{}\"/>\n", tile.cat.as_str()));
+        out.push_str(&format!("    <property name=\"walkable\" type=\"bool\" value=\"{}\"/>\n", tile.walkable));
+        out.push_str(&format!("    <property name=\"bridge\" type=\"bool\" value=\"{}\"/>\n", tile.bridge));
+        out.push_str(&format!("    <property name=\"dwelling\" type=\"bool\" value=\"{}\"/>\n", tile.dwelling));
+        out.push_str("   </properties>\n  </tile>\n");
+    }
+    out.push_str(" </tileset>\n");
+
+    // Tile layer, GIDs are tile ids offset by firstgid
+    out.push_str(&format!(" <layer id=\"1\" name=\"overworld\" width=\"{}\" height=\"{}\">\n", width, height));
+    out.push_str("  <data encoding=\"csv\">\n");
+    for y in 0..height {
+        let row: Vec<String> = (0..width).map(|x| (tilemap.id(x, y) as u32 + 1).to_string()).collect();
+        out.push_str(&row.join(","));
+        if y < height - 1 {
+            out.push(',');
+        }
+        out.push('\n');
+    }
+    out.push_str("  </data>\n </layer>\n");
+
+    // Object layer marking towns, castles, caves and bridges, or what is inside dungeons, towns and castles
+    out.push_str(" <objectgroup id=\"2\" name=\"sites\">\n");
+    for (i, (x, y, kind, tile)) in site_objects(tilemap).iter().enumerate() {
+        out.push_str(&format!(
+            "  <object id=\"{}\" name=\"{}\" type=\"{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>\n",
+            i + 1, tile.name, kind, x * TILE_SIZE as u32, y * TILE_SIZE as u32, TILE_SIZE, TILE_SIZE
+        ));
+    }
+    out.push_str(" </objectgroup>\n");
+
+    out.push_str("</map>\n");
+
+    fs::write(path, out)?;
+
+    Ok(())
+}
+
+// Tiled map editor JSON format (.tmj)
+pub fn export_tmj(tilemap: &Tilemap, tilelist: &Tilelist, tileset: &Path, path: &Path) -> Result<(), Error> {
+
+    let sheet = SpriteSheet::new(tileset, tilelist, path)?;
+    let width = tilemap.width();
+    let height = tilemap.height();
+
+    let mut data = vec![];
+    for y in 0..height {
+        for x in 0..width {
+            data.push(tilemap.id(x, y) as u32 + 1);
+        }
+    }
+
+    let tiles: Vec<_> = tilelist.tiles().iter().map(|tile| json!({
+        "id": tile.id,
+        "properties": [
+            { "name": "name", "type": "string", "value": tile.name },
+            { "name": "cat", "type": "string", "value": tile.cat.as_str() },
+            { "name": "walkable", "type": "bool", "value": tile.walkable },
+            { "name": "bridge", "type": "bool", "value": tile.bridge },
+            { "name": "dwelling", "type": "bool", "value": tile.dwelling },
+        ],
+    })).collect();
+
+    let sites = site_objects(tilemap);
+
+    let objects: Vec<_> = sites.iter().enumerate().map(|(i, (x, y, kind, tile))| json!({
+        "id": i + 1,
+        "name": tile.name,
+        "type": kind,
+        "x": x * TILE_SIZE as u32,
+        "y": y * TILE_SIZE as u32,
+        "width": TILE_SIZE,
+        "height": TILE_SIZE,
+        "rotation": 0,
+        "visible": true,
+    })).collect();
+
+    let map = json!({
+        "type": "map",
+        "version": "1.4",
+        "orientation": "orthogonal",
+        "renderorder": "right-down",
+        "width": width,
+        "height": height,
+        "tilewidth": TILE_SIZE,
+        "tileheight": TILE_SIZE,
+        "infinite": false,
+        "nextlayerid": 3,
+        "nextobjectid": sites.len() + 1,
+        "tilesets": [{
+            "firstgid": 1,
+            "name": sheet.name,
+            "image": sheet.source,
+            "imagewidth": sheet.width,
+            "imageheight": sheet.height,
+            "tilewidth": TILE_SIZE,
+            "tileheight": TILE_SIZE,
+            "tilecount": sheet.tile_count,
+            "columns": sheet.columns,
+            "margin": 0,
+            "spacing": 0,
+            "tiles": tiles,
+        }],
+        "layers": [
+            {
+                "type": "tilelayer",
+                "id": 1,
+                "name": "overworld",
+                "width": width,
+                "height": height,
+                "x": 0,
+                "y": 0,
+                "opacity": 1,
+                "visible": true,
+                "data": data,
+            },
+            {
+                "type": "objectgroup",
+                "id": 2,
+                "name": "sites",
+                "draworder": "topdown",
+                "x": 0,
+                "y": 0,
+                "opacity": 1,
+                "visible": true,
+                "objects": objects,
+            },
+        ],
+    });
+
+    fs::write(path, serde_json::to_string_pretty(&map).unwrap())?;
+
+    Ok(())
+}
+
+// Sprite sheet details needed by Tiled
+struct SpriteSheet {
+    name: String,
+    source: String,
+    width: u32,
+    height: u32,
+    columns: u32,
+    tile_count: u32,
+}
+
+impl SpriteSheet {
+
+    fn new(tileset: &Path, tilelist: &Tilelist, map_path: &Path) -> Result<SpriteSheet, Error> {
+
+        let (width, height) = image::image_dimensions(tileset)?;
+
+        check_sheet(width, height, tilelist)?;
+        let columns = TILES_WIDE_SPRITE_SHEET as u32;
+
+        Ok(SpriteSheet {
+            name: tileset.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default(),
+            source: relative_path(tileset, map_path),
+            width,
+            height,
+            columns,
+            tile_count: columns * (height / TILE_SIZE as u32),
+        })
+    }
+}
+
+// Path of the tileset image as seen from the directory the map is saved in, 
+// since Tiled resolves it relative to the map file
+fn relative_path(tileset: &Path, map_path: &Path) -> String {
+
+    let map_dir = match map_path.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+
+    let relative = match (fs::canonicalize(tileset), fs::canonicalize(map_dir)) {
+        (Ok(tileset), Ok(map_dir)) => {
+
+            let common = tileset.components().zip(map_dir.components()).take_while(|(a, b)| a == b).count();
+
+            let mut relative = PathBuf::new();
+            for _ in map_dir.components().skip(common) {
+                relative.push("..");
+            }
+            for component in tileset.components().skip(common) {
+                relative.push(component);
+            }
+            relative
+        }
+        _ => tileset.to_path_buf(),
+    };
+
+    relative.to_string_lossy().replace('\\', "/")
+}
+
+// Towns, castles, caves and bridges with their tile coordinates
+fn site_objects(tilemap: &Tilemap) -> Vec<(u32, u32, &'static str, Tile)> {
+
+    let mut sites = vec![];
+
+    for y in 0..tilemap.height() {
+        for x in 0..tilemap.width() {
+
+            let tile = tilemap.get(x, y);
+
+            let kind = if tile.dwelling && tile.name.starts_with("castle") {
+                "castle"
+            } else if tile.dwelling {
+                "town"
+            } else if tile.name.starts_with("cave") {
+                "cave"
+            } else if tile.bridge {
+                "bridge"
+            } else if tile.name.starts_with("stairs") {
+                "stairs"
+            } else if tile.name.starts_with("door") {
+                "door"
+            } else if ["chest", "boss", "well", "throne"].contains(&tile.name) {
+                tile.name
+            } else {
+                continue;
+            };
+
+            sites.push((x, y, kind, tile));
+        }
+    }
+
+    sites
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::sync::Arc;
+
+    // Tiled counts from firstgid, 1, with 0 for an empty cell, so every GID 
+    // is the tile id plus one
+    #[test]
+    fn gids_are_ids_plus_one() {
+
+        let tilelist = Arc::new(Tilelist::old_school());
+        let mut tilemap = Tilemap::new(3, 2, "grass", &tilelist);
+
+        tilemap.set_by_name(1, 0, "town_grass");
+        tilemap.set_by_name(2, 1, "deep_water");
+
+        let tileset = Path::new(env!("CARGO_MANIFEST_DIR")).join("old_school_tiles.png");
+        let tmx = std::env::temp_dir().join(format!("gids_{}.tmx", std::process::id()));
+        let tmj = tmx.with_extension("tmj");
+
+        export_tmx(&tilemap, &tilelist, &tileset, &tmx).unwrap();
+        export_tmj(&tilemap, &tilelist, &tileset, &tmj).unwrap();
+
+        let xml = fs::read_to_string(&tmx).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&tmj).unwrap()).unwrap();
+
+        fs::remove_file(&tmx).unwrap();
+        fs::remove_file(&tmj).unwrap();
+
+        let expected: Vec<u32> = (0..2).flat_map(|y| (0..3).map(move |x| (x, y)))
+            .map(|(x, y)| tilemap.id(x, y) as u32 + 1)
+            .collect();
+
+        let csv = xml.split("<data encoding=\"csv\">").nth(1).unwrap().split("</data>").next().unwrap();
+        let from_tmx: Vec<u32> = csv.split(',').map(|gid| gid.trim().parse().unwrap()).collect();
+        let from_tmj: Vec<u32> = json["layers"][0]["data"].as_array().unwrap().iter()
+            .map(|gid| gid.as_u64().unwrap() as u32)
+            .collect();
+
+        assert_eq!(from_tmx, expected);
+        assert_eq!(from_tmj, expected);
+        assert!(!expected.contains(&0));
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::VecDeque; // Landmasses
+
+use crate::tile::{Tilemap, TileCategory};
+
+// Neighbor of (x, y) in "direction". On a wrapping world the map is a torus 
+// and stepping off one edge comes back in on the other, otherwise edges are 
+// boundaries and stepping off one stays in place.
+pub fn neighbor_coor(x: i32, y: i32, width: u32, height: u32, wrap: bool, direction: &str) -> (u32, u32) {
+
+    let mut coor = (x, y);
+
+    if direction == "up" {
+        coor = (x, y - 1);
+        if coor.1 < 0 {
+            coor.1 = if wrap { height as i32 - 1 } else { 0 };
+        }
+    } else if direction == "down" {
+        coor = (x, y + 1);
+        if coor.1 > height as i32 - 1 {
+            coor.1 = if wrap { 0 } else { height as i32 - 1 };
+        }
+    } else if direction == "left" {
+        coor = (x - 1, y);
+        if coor.0 < 0 {
+            coor.0 = if wrap { width as i32 - 1 } else { 0 };
+        }
+    } else if direction == "right" {
+        coor = (x + 1, y);
+        if coor.0 > width as i32 - 1 {
+            coor.0 = if wrap { 0 } else { width as i32 - 1 };
+        }
+    }
+
+    (coor.0 as u32, coor.1 as u32)
+}
+
+pub fn dist_to_water(
+    tilemap: &Tilemap, 
+    x: u32, 
+    y: u32, 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    direction: &str
+) -> u32 {
+
+    let mut dist: u32 = 0;
+    let mut water_found = false;
+    let mut coor = (x, y);
+
+    let max_dist = if direction == "left" || direction == "right" { width } else { height };
+
+    while !water_found {
+
+        coor = neighbor_coor(coor.0 as i32, coor.1 as i32, width, height, wrap, direction);
+        dist += 1;
+
+        let tile = tilemap.get(coor.0, coor.1);
+
+        if tile.cat == TileCategory::Water {
+
+            water_found = true;
+        }
+
+        if dist >= max_dist {
+            water_found = true;
+        }
+    }
+
+    dist
+}
+
+// The up to 4 neighbors, in the order up, right, down, left. Without 
+// wrapping, neighbors past the edges are left out.
+pub fn neighbors(x: u32, y: u32, width: u32, height: u32, wrap: bool) -> Vec<(u32, u32)> {
+
+    let mut n = Vec::with_capacity(4);
+
+    if y > 0 {
+        n.push((x, y - 1));
+    } else if wrap {
+        n.push((x, height - 1));
+    }
+    if x < width - 1 {
+        n.push((x + 1, y));
+    } else if wrap {
+        n.push((0, y));
+    }
+    if y < height - 1 {
+        n.push((x, y + 1));
+    } else if wrap {
+        n.push((x, 0));
+    }
+    if x > 0 {
+        n.push((x - 1, y));
+    } else if wrap {
+        n.push((width - 1, y));
+    }
+
+    n
+}
+
+// Tile "steps" tiles from (x, y) in direction (dx, dy), None past an edge 
+// of a world that doesn't wrap
+pub fn offset(
+    x: u32, 
+    y: u32, 
+    (dx, dy): (i32, i32), 
+    steps: i32, 
+    width: u32, 
+    height: u32, 
+    wrap: bool
+) -> Option<(u32, u32)> {
+
+    let nx = x as i32 + dx * steps;
+    let ny = y as i32 + dy * steps;
+
+    if wrap {
+        Some((nx.rem_euclid(width as i32) as u32, ny.rem_euclid(height as i32) as u32))
+    } else if nx < 0 || ny < 0 || nx >= width as i32 || ny >= height as i32 {
+        None
+    } else {
+        Some((nx as u32, ny as u32))
+    }
+}
+
+// Distance between two tiles along each axis, going the short way around 
+// on a wrapping world
+pub fn axis_dist(a: (u32, u32), b: (u32, u32), width: u32, height: u32, wrap: bool) -> (u32, u32) {
+
+    let dx = (a.0 as i32 - b.0 as i32).unsigned_abs();
+    let dy = (a.1 as i32 - b.1 as i32).unsigned_abs();
+
+    if wrap {
+        (dx.min(width - dx), dy.min(height - dy))
+    } else {
+        (dx, dy)
+    }
+}
+
+// Landmass of every tile, indexed [x][y] with None for water, and the tiles 
+// of each landmass. Rivers and the bridges over them count as land, so a 
+// river doesn't split the land it runs through into separate landmasses.
+pub fn landmasses(tilemap: &Tilemap, river_map: &[Vec<bool>], wrap: bool) -> (Vec<Vec<Option<usize>>>, Vec<Vec<(u32, u32)>>) {
+
+    let width = tilemap.width();
+    let height = tilemap.height();
+
+    let land = |x: u32, y: u32| river_map[x as usize][y as usize] || !tilemap.get(x, y).is_water();
+
+    let mut landmass = vec![vec![None; height as usize]; width as usize];
+    let mut tiles = vec![];
+
+    for x in 0..width {
+        for y in 0..height {
+
+            if landmass[x as usize][y as usize].is_some() || !land(x, y) {
+                continue;
+            }
+
+            let id = tiles.len();
+            let mut area = vec![];
+            let mut queue = VecDeque::new();
+
+            landmass[x as usize][y as usize] = Some(id);
+            queue.push_back((x, y));
+
+            while let Some((cx, cy)) = queue.pop_front() {
+
+                area.push((cx, cy));
+
+                for (nx, ny) in neighbors(cx, cy, width, height, wrap) {
+                    if landmass[nx as usize][ny as usize].is_none() && land(nx, ny) {
+                        landmass[nx as usize][ny as usize] = Some(id);
+                        queue.push_back((nx, ny));
+                    }
+                }
+            }
+
+            tiles.push(area);
+        }
+    }
+
+    (landmass, tiles)
+}
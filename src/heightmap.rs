@@ -0,0 +1,469 @@
+use terr::heightmap::{Heightmap, diamond_square}; // Diamond Square and Heightmap
+
+use rand::prelude::*; // Random
+use rand_distr::{Normal}; // Random
+use opensimplex_noise_rs::OpenSimplexNoise; // Simplex Noise
+use std::path::Path;
+use std::cmp::{self, Ordering}; // For finding maximum
+use std::f64::consts::PI;
+use std::collections::BinaryHeap; // Priority queue for depression filling
+
+use crate::HEIGHTMAP_RANGE;
+use crate::config::{TerrainConfig, LayoutConfig};
+use crate::error::Error;
+use crate::grid::neighbors;
+use crate::landmask::land_mask;
+use crate::render::test_png_hm;
+
+// Simplex noise at a tile. On a wrapping world the map is laid around a 
+// torus in 4D noise space, so the noise continues seamlessly across edges.
+fn noise_at(
+    noise_generator: &OpenSimplexNoise, 
+    x: u32, 
+    y: u32, 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    scale: f64
+) -> f64 {
+
+    if !wrap {
+        return noise_generator.eval_2d(x as f64 * scale, y as f64 * scale);
+    }
+
+    let angle_x = x as f64 / width as f64 * 2.0 * PI;
+    let angle_y = y as f64 / height as f64 * 2.0 * PI;
+
+    // Circles as long as the map, so blobs keep the same size as in 2D
+    let radius_x = width as f64 * scale / (2.0 * PI);
+    let radius_y = height as f64 * scale / (2.0 * PI);
+
+    noise_generator.eval_4d(
+        radius_x * angle_x.cos(),
+        radius_x * angle_x.sin(),
+        radius_y * angle_y.cos(),
+        radius_y * angle_y.sin()
+    )
+}
+
+pub fn apply_simplex(heightmap: &mut Heightmap<f32>, width: u32, height: u32, wrap: bool, scale: f64, noise_seed: i64) {
+
+    let noise_generator = OpenSimplexNoise::new(Some(noise_seed));
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let noise_val = noise_at(&noise_generator, x, y, width, height, wrap, scale) as f32;
+
+            let new_val = ((noise_val + 1.0) / 2.0) * (HEIGHTMAP_RANGE as f32);
+
+            heightmap.set(x, y, new_val);
+        }
+    }
+}
+
+pub fn blended_heightmap(hm1: Heightmap<f32>, hm2: Heightmap<f32>, width: u32, height: u32) -> Heightmap<f32> {
+
+    let mut new_hm = Heightmap::new_flat((width, height), (0.0, 0.0));
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let val_1 = hm1.get(x, y);
+            let val_2 = hm2.get(x, y);
+
+            let new_val = (val_1 + val_2) / 2.0;
+
+            new_hm.set(x, y, new_val);
+        }
+    }
+
+    new_hm
+}
+
+pub fn normalize_heightmap_to_range(
+    heightmap: &mut Heightmap<f32>, 
+    width: u32, 
+    height: u32, 
+    max_exclusive: u32
+) {
+
+    // Using range found manually, since range function built into Heightmap seems incorrect
+    let mut max = 0.0;
+    for x in 0..width {
+        for y in 0..height {
+            let old_val = heightmap.get(x, y);
+            if old_val > max {
+                max = old_val;
+            }
+        }
+    }
+    let mut min = max;
+    for x in 0..width {
+        for y in 0..height {
+            let old_val = heightmap.get(x, y);
+            if old_val < min {
+                min = old_val;
+            }
+        }
+    }
+
+    let new_max = max - min;
+
+    for x in 0..width {
+        for y in 0..height {
+            let old_val = heightmap.get(x, y);
+            heightmap.set(x, y, ((old_val - min) / new_max) * (max_exclusive as f32 - 1.0));
+        }
+    }
+}
+
+// "terr" only does diamond square on squares of power of 2 + 1, so run it 
+// at the next size that fits and cut the middle out
+fn diamond_square_cropped<R: Rng>(
+    width: u32, 
+    height: u32, 
+    rng: &mut R, 
+    distr: Normal<f32>
+) -> Result<Heightmap<f32>, Error> {
+
+    let size = (cmp::max(width, height) - 1).next_power_of_two() + 1;
+
+    // Initiate heightmap at all zeroes
+    let mut full = Heightmap::new_flat((size, size), (0.0, 0.0));
+    diamond_square(&mut full, 0, rng, distr).map_err(|e| Error::Heightmap(format!("{:?}", e)))?;
+
+    if width == size && height == size {
+        return Ok(full);
+    }
+
+    let offset_x = (size - width) / 2;
+    let offset_y = (size - height) / 2;
+
+    let mut heightmap = Heightmap::new_flat((width, height), (0.0, 0.0));
+
+    for x in 0..width {
+        for y in 0..height {
+            heightmap.set(x, y, full.get(x + offset_x, y + offset_y));
+        }
+    }
+
+    Ok(heightmap)
+}
+
+// Diamond square on a torus, so the result tiles seamlessly. The grid is a 
+// power of 2 in size with the far edges wrapping back to the first row and 
+// column, then stretched to the map size.
+fn diamond_square_periodic<R: Rng>(width: u32, height: u32, rng: &mut R, distr: Normal<f32>) -> Heightmap<f32> {
+
+    let size = cmp::max(width, height).next_power_of_two();
+    let index = |x: u32, y: u32| ((y % size) * size + x % size) as usize;
+
+    let mut cells = vec![0.0; (size * size) as usize];
+    let mut step = size;
+    let mut spread = 1.0;
+
+    // All four corners are the same cell on a torus
+    cells[0] = distr.sample(rng);
+
+    while step > 1 {
+
+        let half = step / 2;
+
+        // Square step, the centers of squares
+        for x in (half..size).step_by(step as usize) {
+            for y in (half..size).step_by(step as usize) {
+
+                let avg = (cells[index(x - half, y - half)] + cells[index(x + half, y - half)] +
+                    cells[index(x - half, y + half)] + cells[index(x + half, y + half)]) / 4.0;
+
+                cells[index(x, y)] = avg + distr.sample(rng) * spread;
+            }
+        }
+
+        // Diamond step, the middles of square edges
+        for x in (0..size).step_by(half as usize) {
+            for y in ((x + half) % step..size).step_by(step as usize) {
+
+                let avg = (cells[index(x + size - half, y)] + cells[index(x + half, y)] +
+                    cells[index(x, y + size - half)] + cells[index(x, y + half)]) / 4.0;
+
+                cells[index(x, y)] = avg + distr.sample(rng) * spread;
+            }
+        }
+
+        step = half;
+        spread /= 2.0;
+    }
+
+    let mut heightmap = Heightmap::new_flat((width, height), (0.0, 0.0));
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let fx = x as f32 * size as f32 / width as f32;
+            let fy = y as f32 * size as f32 / height as f32;
+
+            let (x0, y0) = (fx as u32, fy as u32);
+            let (tx, ty) = (fx - x0 as f32, fy - y0 as f32);
+
+            let top = cells[index(x0, y0)] * (1.0 - tx) + cells[index(x0 + 1, y0)] * tx;
+            let bottom = cells[index(x0, y0 + 1)] * (1.0 - tx) + cells[index(x0 + 1, y0 + 1)] * tx;
+
+            heightmap.set(x, y, top * (1.0 - ty) + bottom * ty);
+        }
+    }
+
+    heightmap
+}
+
+fn diamond_square_map<R: Rng>(
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    rng: &mut R, 
+    distr: Normal<f32>
+) -> Result<Heightmap<f32>, Error> {
+
+    if wrap {
+        Ok(diamond_square_periodic(width, height, rng, distr))
+    } else {
+        diamond_square_cropped(width, height, rng, distr)
+    }
+}
+
+// Builds the final heightmap: diamond square blended with simplex noise, 
+// pushed down to ocean outside the land mask, then combined with a second 
+// diamond-square heightmap for mountains. On a wrapping world every layer 
+// is seamless.
+pub fn build_heightmap<R: Rng>(
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    terrain: &TerrainConfig, 
+    layout: &LayoutConfig, 
+    rng: &mut R, 
+    debug_dir: Option<&Path>
+) -> Result<Heightmap<f32>, Error> {
+
+    // Perform diamond square algorythm on heightmap
+
+    //let distr = Uniform::new(0.0 as f32, 1.0 as f32); // Obvious star pattern
+    //let distr = LogNormal::new(0.0 as f32, 1.0 as f32).unwrap(); // Less obvious star pattern
+    let distr = Normal::new(0.0_f32, 1.0_f32).unwrap(); // No star pattern (best!)
+    let mut heightmap = diamond_square_map(width, height, wrap, rng, distr)?;
+
+    // Reset heightmap to desired range
+    normalize_heightmap_to_range(&mut heightmap, width, height, HEIGHTMAP_RANGE as u32);
+
+    if let Some(dir) = debug_dir {
+        test_png_hm(&heightmap, width, height, dir, "test")?;
+    }
+
+    // Blend heightmap with a simplex noise heightmap
+    let noise_seed: i64 = rng.gen();
+    let noise_generator = OpenSimplexNoise::new(Some(noise_seed));
+    let scale = terrain.noise_scale; // The smaller this number, the larger the blobs
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let old_val = heightmap.get(x, y);
+
+            let noise_val = noise_at(&noise_generator, x, y, width, height, wrap, scale) as f32;
+
+            let adjusted_noise_val = ((noise_val + 1.0) / 2.0) * (HEIGHTMAP_RANGE as f32);
+
+            let diff = adjusted_noise_val - old_val;
+
+            let adjust = diff / 4.0;
+
+            let new_val = old_val + adjust;
+
+            heightmap.set(x, y, new_val);
+        }
+    }
+
+    if let Some(dir) = debug_dir {
+        test_png_hm(&heightmap, width, height, dir, "test2")?;
+    }
+
+    // Gradually make the map ocean outside the land mask, and at the edges 
+    // unless the world wraps
+    let mask = land_mask(width, height, wrap, terrain, layout, rng)?;
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let keep = mask.get(x, y);
+
+            if layout.mask_image.is_some() {
+
+                // Pull heights toward the mask, so coastlines follow the 
+                // image while the heightmap still adds detail
+                let old_val = heightmap.get(x, y);
+                let mut new_val = old_val + (keep * HEIGHTMAP_RANGE as f32 - old_val) * layout.mask_strength;
+                if !wrap && (x == 0 || y == 0 || x == width - 1 || y == height - 1) {
+                    new_val = 0.0;
+                }
+
+                heightmap.set(x, y, new_val);
+
+            } else if keep < 1.0 {
+
+                let mut new_val = heightmap.get(x, y) * keep;
+                if !wrap && (x == 0 || y == 0 || x == width - 1 || y == height - 1) {
+                    new_val = 0.0;
+                }
+
+                heightmap.set(x, y, new_val);
+            }
+        }
+    }
+
+    if let Some(dir) = debug_dir {
+        test_png_hm(&heightmap, width, height, dir, "test3")?;
+    }
+
+    // Reset heightmap to desired range
+    normalize_heightmap_to_range(&mut heightmap, width, height, HEIGHTMAP_RANGE as u32);
+
+    if let Some(dir) = debug_dir {
+        test_png_hm(&heightmap, width, height, dir, "test4")?;
+    }
+
+    // Get another diamond-square heightmap (with no island) and combine with 
+    // original where there is land. Will result in more varied mountains, 
+    // instead of all being in the center of the landmass.
+    let mut heightmap_m = diamond_square_map(width, height, wrap, rng, distr)?;
+
+    // Reset heightmap to desired range
+    normalize_heightmap_to_range(&mut heightmap_m, width, height, HEIGHTMAP_RANGE as u32);
+
+    // Combining new mountain heightmap with original heightmap
+    for x in 0..width {
+        for y in 0..height {
+
+            let orig_val = heightmap.get(x, y);
+            let mountain_val = heightmap_m.get(x, y);
+
+            let mut new_val = orig_val;
+
+            if orig_val >= terrain.cutoff_water {
+
+                let diff = mountain_val - orig_val;
+
+                let adjust = if orig_val >= terrain.cutoff_terrain {
+                    diff / 10.0
+                } else {
+                    diff
+                };
+
+                new_val = orig_val + adjust;
+
+                if new_val < terrain.cutoff_water {
+                    new_val = terrain.cutoff_water;
+                }
+            }
+
+            heightmap.set(x, y, new_val);
+        }
+    }
+
+    if let Some(dir) = debug_dir {
+        test_png_hm(&heightmap, width, height, dir, "test5")?;
+    }
+
+    Ok(heightmap)
+}
+
+// Cell waiting in the priority flood queue, lowest height comes out first
+#[derive(PartialEq)]
+pub struct FloodCell {
+    pub h: f32,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Eq for FloodCell {}
+
+impl Ord for FloodCell {
+
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.h.partial_cmp(&self.h).unwrap_or(Ordering::Equal)
+            .then_with(|| (other.x, other.y).cmp(&(self.x, self.y)))
+    }
+}
+
+impl PartialOrd for FloodCell {
+
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Priority flood depression filling. Raises every pit to the height it 
+// would spill at, plus a tiny slope, so each land cell has a strictly lower 
+// neighbor and following the steepest descent always ends in the sea.
+pub fn fill_depressions(heightmap: &Heightmap<f32>, width: u32, height: u32, wrap: bool, cutoff_water: f32) -> Heightmap<f32> {
+
+    priority_flood(heightmap, width, height, wrap, cutoff_water, 0.001)
+}
+
+// Height water would rise to in every cell before spilling over toward the 
+// sea. Cells above their own height here are in a basin.
+pub fn spill_levels(heightmap: &Heightmap<f32>, width: u32, height: u32, wrap: bool, cutoff_water: f32) -> Heightmap<f32> {
+
+    priority_flood(heightmap, width, height, wrap, cutoff_water, 0.0)
+}
+
+fn priority_flood(
+    heightmap: &Heightmap<f32>, 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    cutoff_water: f32, 
+    slope: f32
+) -> Heightmap<f32> {
+
+    let mut filled = Heightmap::new_flat((width, height), (0.0, 0.0));
+    let mut closed = vec![false; (width * height) as usize];
+    let mut queue = BinaryHeap::new();
+
+    // Flooding starts from the sea, and the map edges unless they wrap
+    for x in 0..width {
+        for y in 0..height {
+
+            let h = heightmap.get(x, y);
+
+            filled.set(x, y, h);
+
+            let edge = !wrap && (x == 0 || y == 0 || x == width - 1 || y == height - 1);
+
+            if h < cutoff_water || edge {
+                closed[(y * width + x) as usize] = true;
+                queue.push(FloodCell { h, x, y });
+            }
+        }
+    }
+
+    while let Some(cell) = queue.pop() {
+
+        for (nx, ny) in neighbors(cell.x, cell.y, width, height, wrap) {
+
+            let i = (ny * width + nx) as usize;
+
+            if closed[i] {
+                continue;
+            }
+            closed[i] = true;
+
+            let h = heightmap.get(nx, ny).max(cell.h + slope);
+
+            filled.set(nx, ny, h);
+            queue.push(FloodCell { h, x: nx, y: ny });
+        }
+    }
+
+    filled
+}
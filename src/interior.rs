@@ -0,0 +1,418 @@
+use rand::prelude::*; // Random
+use std::collections::VecDeque; // Catchments and paths, nearest first
+use std::sync::Arc; // Tile list shared with the overworld
+
+use crate::config::InteriorConfig;
+use crate::grid::neighbors;
+use crate::tile::{Tilemap, Tilelist};
+
+// Inside of the town or castle at "site" on the overworld. The player 
+// arrives at the gate. Places are the doors of the inn, shop and church, 
+// the well and the throne, spawns are where NPCs stand.
+pub struct Interior {
+    pub site: (u32, u32),
+    pub castle: bool,
+    pub tilemap: Tilemap,
+    pub entrance: (u32, u32),
+    pub places: Vec<(&'static str, (u32, u32))>,
+    pub spawns: Vec<(u32, u32)>,
+}
+
+// Walkable overworld tiles closer to each dwelling than to any other, up 
+// to "radius" steps away. Dwellings serving more land are more important.
+fn catchments(tilemap: &Tilemap, sites: &[(u32, u32)], wrap: bool, radius: u32) -> Vec<u32> {
+
+    let width = tilemap.width();
+    let height = tilemap.height();
+
+    let mut dist = vec![vec![u32::MAX; height as usize]; width as usize];
+    let mut counts = vec![0; sites.len()];
+    let mut queue = VecDeque::new();
+
+    for (i, &(x, y)) in sites.iter().enumerate() {
+        dist[x as usize][y as usize] = 0;
+        queue.push_back((x, y, i));
+    }
+
+    while let Some((x, y, i)) = queue.pop_front() {
+
+        counts[i] += 1;
+
+        let d = dist[x as usize][y as usize];
+
+        if d == radius {
+            continue;
+        }
+
+        for (nx, ny) in neighbors(x, y, width, height, wrap) {
+            if dist[nx as usize][ny as usize] == u32::MAX && tilemap.get(nx, ny).walkable {
+                dist[nx as usize][ny as usize] = d + 1;
+                queue.push_back((nx, ny, i));
+            }
+        }
+    }
+
+    counts
+}
+
+fn lerp(min: u32, max: u32, t: f32) -> u32 {
+
+    min + ((max - min) as f32 * t).round() as u32
+}
+
+// Rectangular buildings of roof tiles on open ground, at least one tile 
+// apart so the ground between them stays connected. The door is in the 
+// middle of the bottom side. Returns the doors.
+fn place_buildings<R: Rng>(
+    tilemap: &mut Tilemap, 
+    ground: u16, 
+    roof: &str, 
+    count: u32, 
+    rng: &mut R
+) -> Vec<(u32, u32)> {
+
+    let size = tilemap.width();
+    let mut doors = vec![];
+
+    for _ in 0..count * 20 {
+
+        if doors.len() == count as usize {
+            break;
+        }
+
+        let w = rng.gen_range(3, 7);
+        let h = rng.gen_range(3, 6);
+        let x = rng.gen_range(2, size - 2 - w);
+        let y = rng.gen_range(2, size - 2 - h);
+
+        let free = (x - 1..=x + w).all(|bx| (y - 1..=y + h).all(|by| tilemap.id(bx, by) == ground));
+
+        if !free {
+            continue;
+        }
+
+        for bx in x..x + w {
+            for by in y..y + h {
+                tilemap.set_by_name(bx, by, roof);
+            }
+        }
+
+        let door = (x + w / 2, y + h - 1);
+
+        tilemap.set_by_name(door.0, door.1, "door");
+        doors.push(door);
+    }
+
+    doors
+}
+
+// Path from the front of every door to the nearest path tile, over open 
+// ground
+fn connect_doors(tilemap: &mut Tilemap, tilelist: &Tilelist, ground: u16, doors: &[(u32, u32)]) {
+
+    let size = tilemap.width();
+    let path = tilelist.id_at_name("town_path").unwrap();
+
+    for &(x, y) in doors.iter() {
+
+        let start = (x, y + 1);
+        let mut from = vec![vec![None; size as usize]; size as usize];
+        let mut queue = VecDeque::new();
+
+        from[start.0 as usize][start.1 as usize] = Some(start);
+        queue.push_back(start);
+
+        while let Some((cx, cy)) = queue.pop_front() {
+
+            if tilemap.id(cx, cy) == path {
+
+                let mut tile = (cx, cy);
+
+                while tile != start {
+                    tile = from[tile.0 as usize][tile.1 as usize].unwrap();
+                    tilemap.set_id(tile.0, tile.1, path);
+                }
+
+                break;
+            }
+
+            for (nx, ny) in neighbors(cx, cy, size, size, false) {
+
+                let id = tilemap.id(nx, ny);
+
+                if from[nx as usize][ny as usize].is_none() && (id == ground || id == path) {
+                    from[nx as usize][ny as usize] = Some((cx, cy));
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+    }
+}
+
+fn fill(tilemap: &mut Tilemap, x: u32, y: u32, w: u32, h: u32, name: &str) {
+
+    for fx in x..x + w {
+        for fy in y..y + h {
+            tilemap.set_by_name(fx, fy, name);
+        }
+    }
+}
+
+// Ground of the overworld tile inside a stone wall, gate in the middle of 
+// the bottom wall
+fn walled<R: Rng>(tilelist: &Arc<Tilelist>, size: u32, ground: &str, rng: &mut R) -> (Tilemap, (u32, u32)) {
+
+    let mut tilemap = Tilemap::new(size, size, ground, tilelist);
+
+    for i in 0..size {
+        tilemap.set_by_name(i, 0, "town_wall");
+        tilemap.set_by_name(i, size - 1, "town_wall");
+        tilemap.set_by_name(0, i, "town_wall");
+        tilemap.set_by_name(size - 1, i, "town_wall");
+    }
+
+    // Gates a tile off center now and then, so not every town is symmetric
+    let gate = (size / 2 + rng.gen_range(0, 2), size - 1);
+
+    tilemap.set_by_name(gate.0, gate.1, "town_gate");
+
+    (tilemap, gate)
+}
+
+// Open ground and paths NPCs can stand on, shuffled
+fn npc_spawns<R: Rng>(tilemap: &Tilemap, tilelist: &Tilelist, ground: u16, count: usize, rng: &mut R) -> Vec<(u32, u32)> {
+
+    let path = tilelist.id_at_name("town_path").unwrap();
+    let floor = tilelist.id_at_name("castle_floor").unwrap();
+    let size = tilemap.width();
+
+    let mut open = vec![];
+
+    for x in 1..size - 1 {
+        for y in 1..size - 1 {
+            let id = tilemap.id(x, y);
+            if id == ground || id == path || id == floor {
+                open.push((x, y));
+            }
+        }
+    }
+
+    open.shuffle(rng);
+    open.truncate(count);
+    open
+}
+
+// Streets cross at a plaza with a well, houses fill the rest. The three 
+// first buildings become the inn, the shop and, in important enough 
+// towns, the church.
+fn build_town<R: Rng>(
+    tilelist: &Arc<Tilelist>, 
+    site: (u32, u32), 
+    ground: &str, 
+    importance: f32, 
+    config: &InteriorConfig, 
+    rng: &mut R
+) -> Interior {
+
+    let size = lerp(config.town_min_size, config.town_max_size, importance);
+    let (mut tilemap, gate) = walled(tilelist, size, ground, rng);
+    let ground_id = tilelist.id_at_name(ground).unwrap();
+    let mid = size / 2;
+
+    fill(&mut tilemap, gate.0, 1, 1, size - 2, "town_path");
+    fill(&mut tilemap, 1, mid, size - 2, 1, "town_path");
+    fill(&mut tilemap, gate.0 - 1, mid - 1, 3, 3, "town_path");
+    tilemap.set_by_name(gate.0, mid, "well");
+
+    let count = lerp(config.min_buildings, config.max_buildings, importance);
+    let roof = format!("roof_{}", tilelist.tile_at_name(ground).unwrap().cat.ground());
+    let doors = place_buildings(&mut tilemap, ground_id, &roof, count, rng);
+
+    connect_doors(&mut tilemap, tilelist, ground_id, &doors);
+
+    let mut places = vec![("well", (gate.0, mid))];
+    let church = importance >= config.church_above;
+
+    for (&door, &kind) in doors.iter().zip(["inn", "shop", "church"].iter()) {
+
+        if kind == "church" && !church {
+            break;
+        }
+
+        tilemap.set_by_name(door.0, door.1, &format!("door_{}", kind));
+        places.push((kind, door));
+    }
+
+    let npcs = (doors.len() as f32 * config.npcs_per_building).round() as usize;
+    let spawns = npc_spawns(&tilemap, tilelist, ground_id, npcs, rng);
+
+    Interior { site, castle: false, tilemap, entrance: gate, places, spawns }
+}
+
+// Towers on the corners of the outer wall, a keep with a carpet up to the 
+// throne, and a courtyard with a well and a few outbuildings
+fn build_castle<R: Rng>(
+    tilelist: &Arc<Tilelist>, 
+    site: (u32, u32), 
+    ground: &str, 
+    importance: f32, 
+    config: &InteriorConfig, 
+    rng: &mut R
+) -> Interior {
+
+    let size = lerp(config.castle_min_size, config.castle_max_size, importance);
+    let (mut tilemap, gate) = walled(tilelist, size, ground, rng);
+    let ground_id = tilelist.id_at_name(ground).unwrap();
+
+    for &(x, y) in [(0, 0), (size - 2, 0), (0, size - 2), (size - 2, size - 2)].iter() {
+        fill(&mut tilemap, x, y, 2, 2, "tower");
+    }
+
+    // Keep in the upper part of the courtyard, the door lined up with the gate
+    let keep_w = (size / 2) | 1;
+    let keep_h = size * 2 / 5;
+    let keep_x = gate.0 - keep_w / 2;
+    let keep_y = 3;
+
+    fill(&mut tilemap, keep_x, keep_y, keep_w, keep_h, "town_wall");
+    fill(&mut tilemap, keep_x + 1, keep_y + 1, keep_w - 2, keep_h - 2, "castle_floor");
+
+    for &(x, y) in [(keep_x, keep_y), (keep_x + keep_w - 1, keep_y), (keep_x, keep_y + keep_h - 1), (keep_x + keep_w - 1, keep_y + keep_h - 1)].iter() {
+        tilemap.set_by_name(x, y, "tower");
+    }
+
+    let keep_door = (gate.0, keep_y + keep_h - 1);
+    let throne = (gate.0, keep_y + 1);
+
+    tilemap.set_by_name(keep_door.0, keep_door.1, "town_gate");
+    fill(&mut tilemap, gate.0, throne.1 + 1, 1, keep_door.1 - throne.1 - 1, "carpet");
+    tilemap.set_by_name(throne.0, throne.1, "throne");
+    fill(&mut tilemap, gate.0, keep_door.1 + 1, 1, gate.1 - keep_door.1 - 1, "town_path");
+
+    // Path under the guards on either side of the keep door, so no building 
+    // goes up where they stand
+    let guards = [(keep_door.0 - 1, keep_door.1 + 1), (keep_door.0 + 1, keep_door.1 + 1)];
+
+    for &(x, y) in guards.iter() {
+        tilemap.set_by_name(x, y, "town_path");
+    }
+
+    let well = (size / 4, size * 3 / 4);
+
+    tilemap.set_by_name(well.0, well.1, "well");
+
+    let count = lerp(config.min_buildings, config.max_buildings, importance) / 2;
+    let roof = format!("roof_{}", tilelist.tile_at_name(ground).unwrap().cat.ground());
+    let doors = place_buildings(&mut tilemap, ground_id, &roof, count, rng);
+
+    connect_doors(&mut tilemap, tilelist, ground_id, &doors);
+    connect_doors(&mut tilemap, tilelist, ground_id, &[well]);
+
+    // Guards by the keep door, the rest anywhere
+    let npcs = (doors.len() as f32 * config.npcs_per_building).round() as usize;
+
+    let mut spawns = guards.to_vec();
+    spawns.extend(npc_spawns(&tilemap, tilelist, ground_id, npcs, rng).into_iter().filter(|t| !guards.contains(t)));
+
+    let places = vec![("throne", throne), ("well", well)];
+
+    Interior { site, castle: true, tilemap, entrance: gate, places, spawns }
+}
+
+// An interior for every town and castle on the overworld, in reading order. 
+// Sizes follow how much land a site serves, the ground and roofs follow the 
+// ground it stands on.
+pub fn build_interiors<R: Rng>(tilemap: &Tilemap, wrap: bool, config: &InteriorConfig, rng: &mut R) -> Vec<Interior> {
+
+    let mut sites = vec![];
+
+    for y in 0..tilemap.height() {
+        for x in 0..tilemap.width() {
+            if tilemap.get(x, y).dwelling {
+                sites.push((x, y));
+            }
+        }
+    }
+
+    let counts = catchments(tilemap, &sites, wrap, config.catchment_radius);
+    let most = counts.iter().copied().max().unwrap_or(1) as f32;
+
+    sites.iter().zip(counts.iter()).map(|(&(x, y), &count)| {
+
+        let tile = tilemap.get(x, y);
+        let ground = match tile.cat.ground() {
+            "grass" => "grass",
+            "sand" => "sand_0000",
+            _ => "snow_0000",
+        };
+        let importance = count as f32 / most;
+
+        if tile.name.starts_with("castle") {
+            build_castle(tilemap.tilelist(), (x, y), ground, importance, config, rng)
+        } else {
+            build_town(tilemap.tilelist(), (x, y), ground, importance, config, rng)
+        }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use rand_chacha::ChaCha8Rng;
+
+    // The gate is in the outer wall and every door, the well and the 
+    // throne can be walked up to from it. NPCs stand on walkable tiles.
+    #[test]
+    fn every_place_reachable_from_gate() {
+
+        let mut overworld = Tilemap::new(30, 30, "grass", &Arc::new(Tilelist::old_school()));
+
+        for &(x, y, name) in [(3, 3, "town_grass"), (20, 5, "castle_grass"), (8, 22, "town_sand"), (25, 25, "castle_snow"), (15, 15, "town_grass")].iter() {
+            overworld.set_by_name(x, y, name);
+        }
+
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let interiors = build_interiors(&overworld, false, &InteriorConfig::default(), &mut rng);
+
+        assert_eq!(interiors.len(), 5);
+
+        for interior in interiors.iter() {
+
+            let tilemap = &interior.tilemap;
+            let (width, height) = (tilemap.width(), tilemap.height());
+            let (ex, ey) = interior.entrance;
+
+            assert!(ex == 0 || ey == 0 || ex == width - 1 || ey == height - 1, "entrance {:?} inside the map", interior.entrance);
+            assert!(tilemap.get(ex, ey).walkable);
+
+            let mut seen = vec![vec![false; height as usize]; width as usize];
+            let mut queue = VecDeque::new();
+
+            seen[ex as usize][ey as usize] = true;
+            queue.push_back((ex, ey));
+
+            while let Some((x, y)) = queue.pop_front() {
+                for (nx, ny) in neighbors(x, y, width, height, false) {
+                    if !seen[nx as usize][ny as usize] && tilemap.get(nx, ny).walkable {
+                        seen[nx as usize][ny as usize] = true;
+                        queue.push_back((nx, ny));
+                    }
+                }
+            }
+
+            for &(x, y) in interior.spawns.iter() {
+                assert!(tilemap.get(x, y).walkable, "NPC on {} at {:?} in the interior of {:?}", tilemap.get(x, y).name, (x, y), interior.site);
+            }
+
+            // Wells and thrones can't be stood on, only next to
+            for &(kind, (x, y)) in interior.places.iter() {
+
+                let reached = seen[x as usize][y as usize] ||
+                    neighbors(x, y, width, height, false).iter().any(|n| seen[n.0 as usize][n.1 as usize]);
+
+                assert!(reached, "{} at {:?} in the interior of {:?} is cut off", kind, (x, y), interior.site);
+            }
+        }
+    }
+}
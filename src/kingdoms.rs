@@ -0,0 +1,248 @@
+use rand::prelude::*; // Random
+use std::collections::BinaryHeap; // Open set of the border search
+
+use crate::config::KingdomConfig;
+use crate::grid::neighbors;
+use crate::heightmap::FloodCell;
+use crate::names::Name;
+use crate::tile::{Tile, Tilemap, TileCategory};
+
+// Land ruled from one castle. Biomes are counted in tiles, largest first.
+pub struct Kingdom {
+    pub capital: (u32, u32),
+    pub name: Option<String>, // The capital's name without "Castle", when names are on
+    pub towns: Vec<(u32, u32)>,
+    pub area: u32, // Land tiles
+    pub biomes: Vec<(&'static str, u32)>,
+}
+
+// Kingdoms and which one every tile belongs to, indexed [x][y]. Water is 
+// left out, except for rivers and bridges.
+pub struct Kingdoms {
+    pub kingdoms: Vec<Kingdom>,
+    pub map: Vec<Vec<Option<u32>>>,
+}
+
+fn biome(tile: Tile) -> &'static str {
+
+    if tile.name.starts_with("mountain") {
+        "mountains"
+    } else if tile.name.starts_with("hill") {
+        "hills"
+    } else if tile.name.ends_with("forest") {
+        "forest"
+    } else if tile.name == "tundra" {
+        "tundra"
+    } else {
+        match tile.cat {
+            TileCategory::Swamp => "swamp",
+            TileCategory::Sand => "desert",
+            TileCategory::Snow => "snow",
+            TileCategory::Ice => "ice",
+            _ => "grassland",
+        }
+    }
+}
+
+// Cost of a kingdom spreading onto a tile. Mountains and rivers are 
+// expensive so they end up as borders, the sea more so, but islands 
+// without a castle of their own still go to someone.
+fn spread_cost(tile: Tile, river: bool, config: &KingdomConfig) -> f32 {
+
+    if tile.bridge {
+        1.0
+    } else if river {
+        config.river_cost
+    } else if tile.is_water() {
+        config.sea_cost
+    } else if tile.name.starts_with("mountain") {
+        config.mountain_cost
+    } else if tile.name.starts_with("hill") {
+        config.hill_cost
+    } else {
+        1.0
+    }
+}
+
+// Every castle is a capital. Kingdoms grow from their capitals at once, 
+// each tile going to whichever gets there cheapest, with the cost divided 
+// by a random strength so some kingdoms reach further than others.
+pub fn build_kingdoms<R: Rng>(
+    tilemap: &Tilemap, 
+    river_map: &[Vec<bool>], 
+    wrap: bool, 
+    names: &[Name], 
+    config: &KingdomConfig, 
+    rng: &mut R
+) -> Kingdoms {
+
+    let width = tilemap.width();
+    let height = tilemap.height();
+    let index = |x: u32, y: u32| (y * width + x) as usize;
+
+    let mut capitals = vec![];
+
+    for y in 0..height {
+        for x in 0..width {
+
+            let tile = tilemap.get(x, y);
+
+            if tile.dwelling && tile.name.starts_with("castle") {
+                capitals.push((x, y));
+            }
+        }
+    }
+
+    let strengths: Vec<f32> = capitals.iter()
+        .map(|_| 1.0 + config.strength_variance * (rng.gen::<f32>() * 2.0 - 1.0))
+        .collect();
+
+    let mut cost = vec![f32::INFINITY; (width * height) as usize];
+    let mut owner = vec![None; (width * height) as usize];
+    let mut open = BinaryHeap::new();
+
+    for (i, &(x, y)) in capitals.iter().enumerate() {
+        cost[index(x, y)] = 0.0;
+        owner[index(x, y)] = Some(i as u32);
+        open.push(FloodCell { h: 0.0, x, y });
+    }
+
+    while let Some(cell) = open.pop() {
+
+        let current = index(cell.x, cell.y);
+
+        if cell.h > cost[current] {
+            continue;
+        }
+
+        let kingdom = owner[current].unwrap();
+
+        for (nx, ny) in neighbors(cell.x, cell.y, width, height, wrap) {
+
+            let step = spread_cost(tilemap.get(nx, ny), river_map[nx as usize][ny as usize], config);
+            let new_cost = cost[current] + step / strengths[kingdom as usize];
+
+            if new_cost < cost[index(nx, ny)] {
+                cost[index(nx, ny)] = new_cost;
+                owner[index(nx, ny)] = Some(kingdom);
+                open.push(FloodCell { h: new_cost, x: nx, y: ny });
+            }
+        }
+    }
+
+    let mut kingdoms: Vec<Kingdom> = capitals.iter().map(|&capital| Kingdom {
+        capital,
+        name: names.iter()
+            .find(|name| name.kind == "castle" && name.at == capital)
+            .map(|name| name.name.trim_start_matches("Castle ").to_string()),
+        towns: vec![],
+        area: 0,
+        biomes: vec![],
+    }).collect();
+
+    let mut map = vec![vec![None; height as usize]; width as usize];
+
+    for y in 0..height {
+        for x in 0..width {
+
+            let tile = tilemap.get(x, y);
+            let land = !tile.is_water();
+
+            // Open sea belongs to nobody, rivers and bridges to the land around them
+            if !land && !tile.bridge && !river_map[x as usize][y as usize] {
+                continue;
+            }
+
+            let kingdom = match owner[index(x, y)] {
+                Some(kingdom) => kingdom,
+                None => continue,
+            };
+
+            map[x as usize][y as usize] = Some(kingdom);
+
+            if !land {
+                continue;
+            }
+
+            let k = &mut kingdoms[kingdom as usize];
+
+            k.area += 1;
+
+            if tile.dwelling && !tile.name.starts_with("castle") {
+                k.towns.push((x, y));
+            }
+
+            match k.biomes.iter_mut().find(|(name, _)| *name == biome(tile)) {
+                Some((_, count)) => *count += 1,
+                None => k.biomes.push((biome(tile), 1)),
+            }
+        }
+    }
+
+    for k in kingdoms.iter_mut() {
+        k.biomes.sort_by_key(|&(name, count)| (std::cmp::Reverse(count), name));
+    }
+
+    Kingdoms { kingdoms, map }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::tile::Tilelist;
+    use rand_chacha::ChaCha8Rng;
+    use std::sync::Arc;
+
+    // A continent split by a mountain range and a river, with three castles 
+    // and a few towns, and an island with no castle off its coast
+    #[test]
+    fn kingdoms_cover_the_land_around_their_capitals() {
+
+        let (width, height) = (40, 30);
+        let mut tilemap = Tilemap::new(width, height, "water_0000", &Arc::new(Tilelist::old_school()));
+        let mut river_map = vec![vec![false; height as usize]; width as usize];
+
+        for x in 2..30 {
+            for y in 2..28 {
+                let name = if x == 12 { "mountain_grass" } else if x == 20 { "water_0000" } else { "grass" };
+                tilemap.set_by_name(x, y, name);
+                river_map[x as usize][y as usize] = x == 20;
+            }
+        }
+        for x in 33..38 {
+            for y in 10..16 {
+                tilemap.set_by_name(x, y, "sand_0000");
+            }
+        }
+
+        tilemap.set_by_name(20, 14, "bridge_left_right");
+
+        for &(x, y, name) in [(5, 5, "castle_grass"), (16, 20, "castle_grass"), (25, 8, "castle_grass"), (8, 22, "town_grass"), (27, 25, "town_grass"), (35, 12, "town_sand")].iter() {
+            tilemap.set_by_name(x, y, name);
+        }
+
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let kingdoms = build_kingdoms(&tilemap, &river_map, false, &[], &KingdomConfig::default(), &mut rng);
+
+        assert_eq!(kingdoms.kingdoms.len(), 3);
+
+        let land = (0..width).flat_map(|x| (0..height).map(move |y| (x, y)))
+            .filter(|&(x, y)| !tilemap.get(x, y).is_water())
+            .count() as u32;
+
+        assert_eq!(kingdoms.kingdoms.iter().map(|k| k.area).sum::<u32>(), land);
+
+        for (i, k) in kingdoms.kingdoms.iter().enumerate() {
+
+            assert_eq!(kingdoms.map[k.capital.0 as usize][k.capital.1 as usize], Some(i as u32));
+            assert_eq!(k.biomes.iter().map(|&(_, count)| count).sum::<u32>(), k.area);
+
+            for &(x, y) in k.towns.iter() {
+                assert_eq!(kingdoms.map[x as usize][y as usize], Some(i as u32));
+            }
+        }
+
+        assert_eq!(kingdoms.kingdoms.iter().map(|k| k.towns.len()).sum::<usize>(), 3);
+    }
+}
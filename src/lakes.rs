@@ -0,0 +1,202 @@
+use terr::heightmap::Heightmap;
+
+use rand::prelude::*; // Random
+use std::collections::BinaryHeap; // Lowest cell of the lake shore first
+
+use crate::config::{TerrainConfig, LakeConfig};
+use crate::grid::neighbors;
+use crate::heightmap::{fill_depressions, spill_levels, FloodCell};
+use crate::rivers::downhill_course;
+use crate::tile::{Tilemap, TileCategory};
+
+// Fill basins in the heightmap with water up to the level they would spill 
+// over at, returning a binary map of lake placement. Lakes that fill up all 
+// the way drain toward the sea with an outflow river, which is added to the 
+// river map.
+pub fn place_lakes<R: Rng>(
+    tilemap: &mut Tilemap, 
+    heightmap: &Heightmap<f32>, 
+    river_map: &mut [Vec<bool>], 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    terrain: &TerrainConfig, 
+    lake: &LakeConfig, 
+    rng: &mut R
+) -> Vec<Vec<bool>> {
+
+    let spill = spill_levels(heightmap, width, height, wrap, terrain.cutoff_water);
+    let surface = fill_depressions(heightmap, width, height, wrap, terrain.cutoff_water);
+
+    let in_basin = |x: u32, y: u32| spill.get(x, y) > heightmap.get(x, y);
+
+    let mut lake_map = vec![vec![false; height as usize]; width as usize];
+    let mut seen = vec![false; (width * height) as usize];
+    let mut queued = vec![false; (width * height) as usize];
+
+    for x in 0..width {
+        for y in 0..height {
+
+            if seen[(y * width + x) as usize] || !in_basin(x, y) {
+                continue;
+            }
+
+            // Collect the whole basin, remembering its deepest cell
+            let mut basin = vec![(x, y)];
+            let mut deepest = (x, y);
+            let mut next = 0;
+
+            seen[(y * width + x) as usize] = true;
+
+            while next < basin.len() {
+
+                let (bx, by) = basin[next];
+                next += 1;
+
+                if heightmap.get(bx, by) < heightmap.get(deepest.0, deepest.1) {
+                    deepest = (bx, by);
+                }
+
+                for (nx, ny) in neighbors(bx, by, width, height, wrap) {
+                    if !seen[(ny * width + nx) as usize] && in_basin(nx, ny) {
+                        seen[(ny * width + nx) as usize] = true;
+                        basin.push((nx, ny));
+                    }
+                }
+            }
+
+            let depth = spill.get(deepest.0, deepest.1) - heightmap.get(deepest.0, deepest.1);
+
+            if depth < lake.min_depth || rng.gen::<f32>() >= lake.frequency {
+                continue;
+            }
+
+            // Let the water rise from the deepest cell, so a lake that hits 
+            // the size limit covers the lowest part of the basin
+            queued[(deepest.1 * width + deepest.0) as usize] = true;
+
+            let mut shore = BinaryHeap::new();
+            shore.push(FloodCell { h: heightmap.get(deepest.0, deepest.1), x: deepest.0, y: deepest.1 });
+
+            let mut lake_cells = vec![];
+
+            while let Some(cell) = shore.pop() {
+
+                if lake_cells.len() >= lake.max_size as usize {
+                    break;
+                }
+
+                lake_cells.push((cell.x, cell.y));
+
+                for (nx, ny) in neighbors(cell.x, cell.y, width, height, wrap) {
+                    if in_basin(nx, ny) && !queued[(ny * width + nx) as usize] {
+                        queued[(ny * width + nx) as usize] = true;
+                        shore.push(FloodCell { h: heightmap.get(nx, ny), x: nx, y: ny });
+                    }
+                }
+            }
+
+            for &(lx, ly) in lake_cells.iter() {
+                tilemap.set_by_name(lx, ly, "water_0000");
+                lake_map[lx as usize][ly as usize] = true;
+            }
+
+            // Only a lake that filled its basin overflows
+            if lake_cells.len() < basin.len() {
+                continue;
+            }
+
+            // The lowest cell on the rim is where the water spills out
+            let outlet = lake_cells.iter()
+                .flat_map(|&(lx, ly)| neighbors(lx, ly, width, height, wrap))
+                .filter(|&(nx, ny)| !lake_map[nx as usize][ny as usize])
+                .min_by(|a, b| heightmap.get(a.0, a.1).partial_cmp(&heightmap.get(b.0, b.1)).unwrap());
+
+            if let Some(outlet) = outlet {
+
+                if tilemap.get(outlet.0, outlet.1).cat == TileCategory::Water {
+                    continue;
+                }
+
+                for (rx, ry) in downhill_course(tilemap, &surface, outlet, width, height, wrap) {
+                    tilemap.set_by_name(rx, ry, "water_0000");
+                    river_map[rx as usize][ry as usize] = true;
+                }
+            }
+        }
+    }
+
+    lake_map
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::tile::Tilelist;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use std::sync::Arc;
+
+    // Land rising away from the sea on the left, with a pit in the middle 
+    // that fills up and overflows
+    #[test]
+    fn overflowing_lake_drains_to_sea() {
+
+        let (width, height) = (20, 20);
+        let terrain = TerrainConfig::default();
+        let lake = LakeConfig { frequency: 1.0, ..LakeConfig::default() };
+
+        let mut heightmap = Heightmap::new_flat((width, height), (0.0, 0.0));
+        let mut tilemap = Tilemap::new(width, height, "grass", &Arc::new(Tilelist::old_school()));
+        let mut river_map = vec![vec![false; height as usize]; width as usize];
+
+        for x in 0..width {
+            for y in 0..height {
+
+                let pit = (9..12).contains(&x) && (9..12).contains(&y);
+                let h = if x == 0 { 10.0 } else if pit { 55.0 } else { 60.0 + x as f32 * 2.0 };
+
+                heightmap.set(x, y, h);
+
+                if h < terrain.cutoff_water {
+                    tilemap.set_by_name(x, y, "water_0000");
+                }
+            }
+        }
+
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let lake_map = place_lakes(&mut tilemap, &heightmap, &mut river_map, width, height, false, &terrain, &lake, &mut rng);
+
+        assert_eq!(lake_map.iter().flatten().filter(|&&l| l).count(), 9);
+
+        // Follow the outflow river from the lake shore
+        let mut course: Vec<(u32, u32)> = (0..width)
+            .flat_map(|x| (0..height).map(move |y| (x, y)))
+            .filter(|&(x, y)| river_map[x as usize][y as usize] && !lake_map[x as usize][y as usize])
+            .filter(|&(x, y)| neighbors(x, y, width, height, false).iter().any(|n| lake_map[n.0 as usize][n.1 as usize]))
+            .collect();
+
+        assert!(!course.is_empty(), "full lake has no outflow");
+
+        let mut next = 0;
+
+        while next < course.len() {
+
+            let (x, y) = course[next];
+            next += 1;
+
+            for n in neighbors(x, y, width, height, false) {
+                if river_map[n.0 as usize][n.1 as usize] && !course.contains(&n) {
+                    course.push(n);
+                }
+            }
+        }
+
+        let reaches_sea = course.iter().any(|&(x, y)| {
+            neighbors(x, y, width, height, false).iter().any(|n| heightmap.get(n.0, n.1) < terrain.cutoff_water)
+        });
+
+        assert!(reaches_sea, "outflow river stops before the sea");
+    }
+}
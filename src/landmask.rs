@@ -0,0 +1,227 @@
+use terr::heightmap::Heightmap;
+
+use rand::prelude::*; // Random
+use std::cmp; // For finding maximum
+use std::path::Path;
+
+use crate::config::{TerrainConfig, Layout, LayoutConfig};
+use crate::error::Error;
+use crate::grid::axis_dist;
+
+// 1 within "radius" of a landmass center, fading to 0 at twice the radius
+fn falloff(dist: f32, radius: f32) -> f32 {
+
+    if dist <= radius {
+        return 1.0;
+    }
+
+    let further = dist - radius;
+
+    ((radius - (further)) / radius).max(0.0)
+}
+
+// Distance between two tiles. On rectangular maps the land area is an 
+// ellipse, so distances are measured as if the map were stretched to a 
+// square of the longer side.
+fn stretched_dist(a: (u32, u32), b: (u32, u32), width: u32, height: u32, wrap: bool) -> f32 {
+
+    let size = cmp::max(width, height);
+    let stretch_x = size as f32 / width as f32;
+    let stretch_y = size as f32 / height as f32;
+
+    let (dx, dy) = axis_dist(a, b, width, height, wrap);
+
+    let dist_x = dx as f32 * stretch_x;
+    let dist_y = dy as f32 * stretch_y;
+
+    (dist_x * dist_x + dist_y * dist_y).sqrt()
+}
+
+// Landmass centers at least "min_separation" tiles apart. When there is no 
+// room for that, the spot furthest from the others out of many tries is used.
+fn place_centers<R: Rng>(
+    count: u32, 
+    min_separation: f32, 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    rng: &mut R
+) -> Vec<(u32, u32)> {
+
+    let mut centers: Vec<(u32, u32)> = vec![];
+
+    // Without wrapping keep landmasses away from the edges, which are ocean
+    let (min_x, max_x, min_y, max_y) = if wrap {
+        (0, width, 0, height)
+    } else {
+        (width * 3 / 20, width * 17 / 20, height * 3 / 20, height * 17 / 20)
+    };
+
+    for _ in 0..count {
+
+        let mut best = (0, 0);
+        let mut best_dist = -1.0;
+
+        for _ in 0..100 {
+
+            let candidate = (rng.gen_range(min_x, max_x), rng.gen_range(min_y, max_y));
+
+            let dist = centers.iter()
+                .map(|&c| stretched_dist(c, candidate, width, height, wrap))
+                .fold(f32::INFINITY, f32::min);
+
+            if dist > best_dist {
+                best = candidate;
+                best_dist = dist;
+            }
+
+            if dist >= min_separation {
+                break;
+            }
+        }
+
+        centers.push(best);
+    }
+
+    centers
+}
+
+// A grayscale image of any size stretched over the map, white is land and 
+// black is sea. The image is sampled between pixels so it doesn't look 
+// blocky when it is smaller than the map.
+fn mask_from_image(path: &Path, width: u32, height: u32, wrap: bool) -> Result<Heightmap<f32>, Error> {
+
+    let image = image::open(path)?.to_luma8();
+    let (image_width, image_height) = image.dimensions();
+
+    // On a wrapping world the right edge blends into the left, otherwise 
+    // the edge pixels are repeated
+    let pixel = |x: i64, y: i64| {
+        let (x, y) = if wrap {
+            (x.rem_euclid(image_width as i64), y.rem_euclid(image_height as i64))
+        } else {
+            (x.clamp(0, image_width as i64 - 1), y.clamp(0, image_height as i64 - 1))
+        };
+        image.get_pixel(x as u32, y as u32)[0] as f32 / 255.0
+    };
+
+    let mut mask = Heightmap::new_flat((width, height), (0.0, 0.0));
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let fx = (x as f32 + 0.5) * image_width as f32 / width as f32 - 0.5;
+            let fy = (y as f32 + 0.5) * image_height as f32 / height as f32 - 0.5;
+
+            let (x0, y0) = (fx.floor() as i64, fy.floor() as i64);
+            let (tx, ty) = (fx - x0 as f32, fy - y0 as f32);
+
+            let top = pixel(x0, y0) * (1.0 - tx) + pixel(x0 + 1, y0) * tx;
+            let bottom = pixel(x0, y0 + 1) * (1.0 - tx) + pixel(x0 + 1, y0 + 1) * tx;
+
+            mask.set(x, y, top * (1.0 - ty) + bottom * ty);
+        }
+    }
+
+    Ok(mask)
+}
+
+// How much of the heightmap to keep in each cell, from 0 to 1. The 
+// heightmap is multiplied with this, so land can only rise where the mask 
+// is high. A wrapping world has no island, the other layouts wrap around. 
+// A mask image replaces the layout.
+pub fn land_mask<R: Rng>(
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    terrain: &TerrainConfig, 
+    layout: &LayoutConfig, 
+    rng: &mut R
+) -> Result<Heightmap<f32>, Error> {
+
+    if let Some(path) = &layout.mask_image {
+        return mask_from_image(path, width, height, wrap);
+    }
+
+    let mut mask = Heightmap::new_flat((width, height), (1.0, 1.0));
+
+    if wrap && layout.mode == Layout::Island {
+        return Ok(mask);
+    }
+
+    let size = cmp::max(width, height) as f32;
+    let land_radius = size * terrain.land_radius;
+    let center = ((width / 2) - 1, (height / 2) - 1);
+
+    // Landmasses as centers and radii, for the layouts made of blobs
+    let mut blobs = vec![];
+
+    match layout.mode {
+        Layout::Continents | Layout::Archipelago => {
+
+            let (count, radius) = if layout.mode == Layout::Continents {
+                (layout.count, land_radius / (layout.count as f32).sqrt() * 0.8)
+            } else {
+                // Small islands with plenty of water between them
+                (layout.count * 5, land_radius / (layout.count as f32 * 5.0).sqrt() * 0.6)
+            };
+
+            let centers = place_centers(count, layout.min_separation * size, width, height, wrap, rng);
+
+            for c in centers {
+                let variance = rng.gen_range(-1.0, 1.0) * layout.size_variance;
+                blobs.push((c, radius * (1.0 + variance)));
+            }
+        }
+        Layout::Pangaea => {
+
+            // Off center, so the open ocean isn't the same on every side
+            let shift_x = rng.gen_range(-(width as i32) / 16, width as i32 / 16 + 1);
+            let shift_y = rng.gen_range(-(height as i32) / 16, height as i32 / 16 + 1);
+
+            let c = ((center.0 as i32 + shift_x) as u32, (center.1 as i32 + shift_y) as u32);
+
+            blobs.push((c, land_radius * 1.3));
+        }
+        _ => {}
+    }
+
+    // Layouts that reach further than the island fade out before the edges, 
+    // so coasts don't run straight along them
+    let edge_fade = size * 0.08;
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let value = match layout.mode {
+                Layout::Island => falloff(stretched_dist(center, (x, y), width, height, wrap), land_radius),
+                Layout::InlandSea => {
+
+                    let dist = stretched_dist(center, (x, y), width, height, wrap);
+                    let outer = land_radius * 1.2;
+
+                    falloff(dist, outer) * (1.0 - falloff(dist, outer * 0.35))
+                }
+                Layout::Ring => {
+
+                    let dist = stretched_dist(center, (x, y), width, height, wrap);
+
+                    falloff((dist - land_radius * 1.1).abs(), land_radius * 0.2)
+                }
+                _ => blobs.iter()
+                    .map(|&(c, radius)| falloff(stretched_dist(c, (x, y), width, height, wrap), radius))
+                    .fold(0.0, f32::max),
+            };
+
+            let edge_dist = cmp::min(cmp::min(x, width - 1 - x), cmp::min(y, height - 1 - y)) as f32;
+
+            if !wrap && layout.mode != Layout::Island && edge_dist < edge_fade {
+                mask.set(x, y, value * edge_dist / edge_fade);
+            } else {
+                mask.set(x, y, value);
+            }
+        }
+    }
+
+    Ok(mask)
+}
@@ -0,0 +1,47 @@
+// Generation stages take the map, its size, their settings and the rng as
+// separate arguments, which easily adds up to more than clippy likes
+#![allow(clippy::too_many_arguments)]
+
+mod climate;
+mod config;
+mod connectivity;
+mod dungeon;
+mod erosion;
+mod error;
+mod export;
+mod font;
+mod grid;
+mod heightmap;
+mod interior;
+mod kingdoms;
+mod lakes;
+mod landmask;
+mod mapfile;
+mod names;
+mod render;
+mod rivers;
+mod roads;
+mod sites;
+mod terrain;
+mod tile;
+mod world;
+
+pub use terr::heightmap::Heightmap;
+
+pub use config::{Config, TerrainConfig, Layout, LayoutConfig, ErosionConfig, BiomeConfig, ClimateConfig, Wind, OceanConfig, RiverConfig, RiverMode, LakeConfig, RoadConfig, ConnectivityConfig, SiteConfig, TownPlacement, DungeonConfig, DungeonStyle, InteriorConfig, NameConfig, KingdomConfig};
+pub use climate::Climate;
+pub use connectivity::{Connectivity, SiteReach};
+pub use dungeon::{Dungeon, Floor};
+pub use error::{Error, Warning};
+pub use export::{export_csv, export_tmx, export_tmj};
+pub use interior::Interior;
+pub use kingdoms::{Kingdom, Kingdoms};
+pub use mapfile::{save_map, load_map, save_dungeon_links, save_interior_links, save_names, save_kingdoms};
+pub use names::Name;
+pub use render::{map_png, render_tilemap, draw_labels, draw_kingdoms, save_png, test_png_hm, test_png_vec, TileAtlas};
+pub use tile::{Tile, TileCategory, Tilemap, Tilelist};
+pub use world::{generate_world, World};
+
+pub const TILE_SIZE: u8 = 16;
+pub const TILES_WIDE_SPRITE_SHEET: u8 = 5;
+pub const HEIGHTMAP_RANGE: u8 = 100;
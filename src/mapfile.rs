@@ -0,0 +1,259 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::dungeon::Dungeon;
+use crate::error::Error;
+use crate::interior::Interior;
+use crate::kingdoms::Kingdom;
+use crate::names::Name;
+use crate::tile::{Tilemap, Tilelist};
+
+// Plain text map format, so a generated map can be rendered or exported 
+// later without generating it again:
+//
+//     seed 42
+//     size 257 257
+//     12 12 12 ... (one line of tile ids per row)
+
+pub fn save_map(tilemap: &Tilemap, seed: u64, path: &Path) -> Result<(), Error> {
+
+    let mut out = String::new();
+
+    out.push_str(&format!("seed {}\n", seed));
+    out.push_str(&format!("size {} {}\n", tilemap.width(), tilemap.height()));
+
+    for y in 0..tilemap.height() {
+
+        let row: Vec<String> = (0..tilemap.width()).map(|x| tilemap.id(x, y).to_string()).collect();
+
+        out.push_str(&row.join(" "));
+        out.push('\n');
+    }
+
+    fs::write(path, out)?;
+
+    Ok(())
+}
+
+pub fn load_map(path: &Path, tilelist: &Arc<Tilelist>) -> Result<(Tilemap, u64), Error> {
+
+    let contents = fs::read_to_string(path)?;
+    let mut lines = contents.lines();
+
+    let seed = match lines.next().and_then(|l| l.strip_prefix("seed ")) {
+        Some(s) => s.trim().parse::<u64>().map_err(|_| Error::MapFile(format!("bad seed \"{}\"", s)))?,
+        None => return Err(Error::MapFile(String::from("missing seed line"))),
+    };
+
+    let size: Vec<u32> = match lines.next().and_then(|l| l.strip_prefix("size ")) {
+        Some(s) => s.split_whitespace().map(|v| v.parse::<u32>()).collect::<Result<_, _>>()
+            .map_err(|_| Error::MapFile(format!("bad size \"{}\"", s)))?,
+        None => return Err(Error::MapFile(String::from("missing size line"))),
+    };
+
+    if size.len() != 2 || size[0] == 0 || size[1] == 0 {
+        return Err(Error::MapFile(String::from("size must be two positive numbers")));
+    }
+
+    let (width, height) = (size[0], size[1]);
+
+    let mut tilemap = Tilemap::new(width, height, "grass", tilelist);
+
+    for y in 0..height {
+
+        let line = lines.next().ok_or_else(|| Error::MapFile(format!("missing row {}", y)))?;
+        let ids: Vec<&str> = line.split_whitespace().collect();
+
+        if ids.len() != width as usize {
+            return Err(Error::MapFile(format!("row {} has {} tiles, expected {}", y, ids.len(), width)));
+        }
+
+        for (x, id) in ids.iter().enumerate() {
+
+            let tile = id.parse::<u16>().ok().and_then(|id| tilelist.tile_at_id(id).ok())
+                .ok_or_else(|| Error::MapFile(format!("unknown tile id \"{}\" in row {}", id, y)))?;
+
+            tilemap.set_id(x as u32, y, tile.id);
+        }
+    }
+
+    Ok((tilemap, seed))
+}
+
+// Links from caves on the overworld to the dungeons behind them, one 
+// dungeon line followed by a line for each of its floors:
+//
+//     dungeon 0 cave 34 120 entry 12 30 floors 2
+//     floor 0 up 12 30 down 40 8
+//     floor 1 up 6 20 boss 33 41
+//
+// The floors themselves are saved as .map files of their own.
+
+pub fn save_dungeon_links(dungeons: &[Dungeon], path: &Path) -> Result<(), Error> {
+
+    let mut out = String::new();
+
+    for (i, dungeon) in dungeons.iter().enumerate() {
+
+        let (cx, cy) = dungeon.cave;
+        let (ex, ey) = dungeon.entry();
+
+        out.push_str(&format!("dungeon {} cave {} {} entry {} {} floors {}\n", i, cx, cy, ex, ey, dungeon.floors.len()));
+
+        for (j, floor) in dungeon.floors.iter().enumerate() {
+
+            out.push_str(&format!("floor {} up {} {}", j, floor.up.0, floor.up.1));
+
+            if let Some((x, y)) = floor.down {
+                out.push_str(&format!(" down {} {}", x, y));
+            }
+            if let Some((x, y)) = floor.boss {
+                out.push_str(&format!(" boss {} {}", x, y));
+            }
+
+            out.push('\n');
+        }
+    }
+
+    fs::write(path, out)?;
+
+    Ok(())
+}
+
+// Links from towns and castles on the overworld to their insides, one 
+// interior line followed by its places and where NPCs stand:
+//
+//     interior 0 town 34 120 entrance 16 31 size 32 32
+//     place inn 10 12
+//     npc 5 7
+//
+// The insides themselves are saved as .map files of their own.
+
+pub fn save_interior_links(interiors: &[Interior], path: &Path) -> Result<(), Error> {
+
+    let mut out = String::new();
+
+    for (i, interior) in interiors.iter().enumerate() {
+
+        let (sx, sy) = interior.site;
+        let (ex, ey) = interior.entrance;
+        let kind = if interior.castle { "castle" } else { "town" };
+
+        out.push_str(&format!(
+            "interior {} {} {} {} entrance {} {} size {} {}\n",
+            i, kind, sx, sy, ex, ey, interior.tilemap.width(), interior.tilemap.height()
+        ));
+
+        for (name, (x, y)) in interior.places.iter() {
+            out.push_str(&format!("place {} {} {}\n", name, x, y));
+        }
+        for (x, y) in interior.spawns.iter() {
+            out.push_str(&format!("npc {} {}\n", x, y));
+        }
+    }
+
+    fs::write(path, out)?;
+
+    Ok(())
+}
+
+// Names of sites and areas, one per line as kind, the tile the name is 
+// labeled at, the size of the area in tiles and the name itself:
+//
+//     castle 40 52 1 Castle Dorwen
+//     river 88 130 64 River Talmere
+
+pub fn save_names(names: &[Name], path: &Path) -> Result<(), Error> {
+
+    let mut out = String::new();
+
+    for name in names.iter() {
+        out.push_str(&format!("{} {} {} {} {}\n", name.kind, name.at.0, name.at.1, name.tiles, name.name));
+    }
+
+    fs::write(path, out)?;
+
+    Ok(())
+}
+
+// Kingdoms, one kingdom line with its capital, its area in land tiles and 
+// its name when names are on, followed by its other towns and the biomes 
+// it covers, largest first:
+//
+//     kingdom 0 capital 40 52 area 3120 Dorwen
+//     town 34 120
+//     biome grassland 1804
+
+pub fn save_kingdoms(kingdoms: &[Kingdom], path: &Path) -> Result<(), Error> {
+
+    let mut out = String::new();
+
+    for (i, kingdom) in kingdoms.iter().enumerate() {
+
+        let (cx, cy) = kingdom.capital;
+
+        out.push_str(&format!("kingdom {} capital {} {} area {}", i, cx, cy, kingdom.area));
+        if let Some(name) = &kingdom.name {
+            out.push_str(&format!(" {}", name));
+        }
+        out.push('\n');
+
+        for (x, y) in kingdom.towns.iter() {
+            out.push_str(&format!("town {} {}\n", x, y));
+        }
+        for (biome, tiles) in kingdom.biomes.iter() {
+            out.push_str(&format!("biome {} {}\n", biome, tiles));
+        }
+    }
+
+    fs::write(path, out)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::config::Config;
+    use crate::world::generate_world;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+
+        std::env::temp_dir().join(format!("{}_{}.map", name, std::process::id()))
+    }
+
+    #[test]
+    fn save_load_round_trip() {
+
+        let config = Config { seed: 3, width: 96, height: 48, roads: true, ..Config::default() };
+        let world = generate_world(&config).unwrap();
+        let path = temp_path("round_trip");
+
+        save_map(&world.tilemap, world.seed, &path).unwrap();
+        let (tilemap, seed) = load_map(&path, &world.tilelist).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(seed, 3);
+        assert_eq!((tilemap.width(), tilemap.height()), (96, 48));
+
+        for y in 0..48 {
+            for x in 0..96 {
+                assert_eq!(tilemap.id(x, y), world.tilemap.id(x, y), "tile ({}, {}) changed", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn load_rejects_unknown_tiles() {
+
+        let path = temp_path("unknown_tile");
+
+        fs::write(&path, "seed 1\nsize 2 1\n0 9999\n").unwrap();
+        let loaded = load_map(&path, &Arc::new(Tilelist::old_school()));
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(loaded, Err(Error::MapFile(_))));
+    }
+}
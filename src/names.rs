@@ -0,0 +1,415 @@
+use rand::prelude::*; // Random
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng; // Seeded random, same sequence on every platform
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::config::NameConfig;
+use crate::grid::{landmasses, neighbors};
+use crate::tile::Tilemap;
+
+// Name of a town, castle, cave, landmass, mountain range, river, lake or 
+// sea. Labels go at "at", the site itself or the middle of the area.
+pub struct Name {
+    pub kind: &'static str,
+    pub name: String,
+    pub at: (u32, u32),
+    pub tiles: u32,
+}
+
+// Sounds names are made of, in the style of one kind of ground
+struct Style {
+    onsets: &'static [&'static str],
+    vowels: &'static [&'static str],
+    codas: &'static [&'static str],
+    town_endings: &'static [&'static str],
+    land_endings: &'static [&'static str],
+}
+
+const GRASS: Style = Style {
+    onsets: &["b", "br", "c", "d", "dr", "f", "g", "h", "l", "m", "n", "r", "s", "t", "th", "v", "w"],
+    vowels: &["a", "e", "i", "o", "a", "e", "i", "o", "ae", "ea", "ie"],
+    codas: &["", "", "l", "n", "r", "th", "nd", "m", "ll", "s"],
+    town_endings: &["ton", "ford", "dale", "wick", "bury", "mere", "stead", "brook", "ham"],
+    land_endings: &["ia", "and", "or", "ell"],
+};
+
+const SAND: Style = Style {
+    onsets: &["b", "d", "h", "j", "k", "kh", "m", "n", "q", "r", "s", "sh", "z"],
+    vowels: &["a", "a", "i", "u", "a", "i", "u", "aa", "ei"],
+    codas: &["", "", "r", "n", "m", "z", "sh", "d", "l"],
+    town_endings: &["abad", "ar", "esh", "im", "an", "ara"],
+    land_endings: &["istan", "ara", "un", "eth"],
+};
+
+const SNOW: Style = Style {
+    onsets: &["b", "d", "fr", "g", "h", "k", "kj", "n", "r", "sk", "st", "sv", "t", "th", "v"],
+    vowels: &["a", "e", "i", "o", "u", "a", "e", "i", "o", "y", "ei", "au"],
+    codas: &["", "", "k", "r", "n", "rn", "ld", "g", "st"],
+    town_endings: &["heim", "gard", "vik", "by", "stad", "holm", "fjord"],
+    land_endings: &["land", "mark", "heim"],
+};
+
+fn style(ground: &str) -> &'static Style {
+
+    match ground {
+        "sand" => &SAND,
+        "snow" => &SNOW,
+        _ => &GRASS,
+    }
+}
+
+fn pick<R: Rng>(options: &[&'static str], rng: &mut R) -> &'static str {
+
+    options.choose(rng).unwrap()
+}
+
+// Syllables of onset and vowel, only the last one ending in a consonant, 
+// never the same as a root used before. Roots get a syllable longer every 
+// 20 tries, once the short ones are running out.
+fn root<R: Rng>(style: &Style, syllables: u32, used: &mut HashSet<String>, rng: &mut R) -> String {
+
+    let mut syllables = syllables;
+    let mut tries = 0;
+
+    loop {
+
+        let mut root = String::new();
+
+        for i in 0..syllables {
+            root.push_str(pick(style.onsets, rng));
+            root.push_str(pick(style.vowels, rng));
+            if i == syllables - 1 {
+                root.push_str(pick(style.codas, rng));
+            }
+        }
+
+        let mut chars = root.chars();
+        let root: String = chars.next().unwrap().to_uppercase().chain(chars).collect();
+
+        if used.insert(root.clone()) {
+            return root;
+        }
+
+        tries += 1;
+
+        if tries % 20 == 0 {
+            syllables += 1;
+        }
+    }
+}
+
+fn full_name<R: Rng>(kind: &str, style: &Style, used: &mut HashSet<String>, rng: &mut R) -> String {
+
+    // Roots that get an ending are kept short
+    let ending = kind == "landmass" || (kind == "town" && rng.gen::<bool>());
+    let syllables = if ending { rng.gen_range(1, 3) } else if rng.gen::<f32>() < 0.8 { 2 } else { 3 };
+
+    let root = root(style, syllables, used, rng);
+    let either = rng.gen::<bool>();
+
+    match kind {
+        "town" if ending => [root.as_str(), pick(style.town_endings, rng)].concat(),
+        "castle" => format!("Castle {}", root),
+        "cave" if either => format!("Cave of {}", root),
+        "cave" => format!("{} Cave", root),
+        "landmass" => [root.trim_end_matches(|c| "aeiouy".contains(c)), pick(style.land_endings, rng)].concat(),
+        "mountains" => format!("{} {}", root, pick(&["Mountains", "Peaks", "Range"], rng)),
+        "river" if either => format!("River {}", root),
+        "river" => format!("{} River", root),
+        "lake" => format!("Lake {}", root),
+        "ocean" => format!("{} Ocean", root),
+        "sea" if either => format!("Sea of {}", root),
+        "sea" => format!("{} Sea", root),
+        _ => root,
+    }
+}
+
+// A full name never given out before. Different roots can still end up 
+// the same once endings are added, like a landmass losing its last vowel.
+fn unique_name<R: Rng>(
+    kind: &str, 
+    style: &Style, 
+    used: &mut HashSet<String>, 
+    taken: &mut HashSet<String>, 
+    rng: &mut R
+) -> String {
+
+    loop {
+
+        let name = full_name(kind, style, used, rng);
+
+        if taken.insert(name.clone()) {
+            return name;
+        }
+    }
+}
+
+// Connected areas of tiles where "inside" holds, each in the order they 
+// were found, starting from its top left tile
+fn areas(width: u32, height: u32, wrap: bool, inside: impl Fn(u32, u32) -> bool) -> Vec<Vec<(u32, u32)>> {
+
+    let mut seen = vec![vec![false; height as usize]; width as usize];
+    let mut areas = vec![];
+
+    for x in 0..width {
+        for y in 0..height {
+
+            if seen[x as usize][y as usize] || !inside(x, y) {
+                continue;
+            }
+
+            let mut area = vec![];
+            let mut queue = VecDeque::new();
+
+            seen[x as usize][y as usize] = true;
+            queue.push_back((x, y));
+
+            while let Some((cx, cy)) = queue.pop_front() {
+
+                area.push((cx, cy));
+
+                for (nx, ny) in neighbors(cx, cy, width, height, wrap) {
+                    if !seen[nx as usize][ny as usize] && inside(nx, ny) {
+                        seen[nx as usize][ny as usize] = true;
+                        queue.push_back((nx, ny));
+                    }
+                }
+            }
+
+            areas.push(area);
+        }
+    }
+
+    areas
+}
+
+// Tile of the area closest to its average position. On a wrapping world an 
+// area across the edge can end up labeled off center.
+fn middle(area: &[(u32, u32)]) -> (u32, u32) {
+
+    let n = area.len() as f32;
+    let cx = area.iter().map(|t| t.0 as f32).sum::<f32>() / n;
+    let cy = area.iter().map(|t| t.1 as f32).sum::<f32>() / n;
+
+    *area.iter().min_by(|a, b| {
+        let da = (a.0 as f32 - cx).powi(2) + (a.1 as f32 - cy).powi(2);
+        let db = (b.0 as f32 - cx).powi(2) + (b.1 as f32 - cy).powi(2);
+        da.partial_cmp(&db).unwrap()
+    }).unwrap()
+}
+
+// Tile halfway along the longest path through a river, from any tile to 
+// the farthest one from it and on to the farthest one from there
+fn halfway(area: &[(u32, u32)], width: u32, height: u32, wrap: bool) -> (u32, u32) {
+
+    let inside: HashSet<(u32, u32)> = area.iter().copied().collect();
+
+    // Path from the farthest tile back to "from"
+    let farthest = |from: (u32, u32)| {
+
+        let mut came_from = HashMap::new();
+        let mut queue = VecDeque::new();
+        let mut last = from;
+
+        came_from.insert(from, from);
+        queue.push_back(from);
+
+        while let Some((cx, cy)) = queue.pop_front() {
+
+            last = (cx, cy);
+
+            for next in neighbors(cx, cy, width, height, wrap) {
+                if inside.contains(&next) && !came_from.contains_key(&next) {
+                    came_from.insert(next, (cx, cy));
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let mut path = vec![last];
+
+        while last != from {
+            last = came_from[&last];
+            path.push(last);
+        }
+
+        path
+    };
+
+    let end = farthest(area[0])[0];
+    let path = farthest(end);
+
+    path[path.len() / 2]
+}
+
+// Most common ground in and around an area, so names along a desert coast 
+// sound like the desert
+fn ground_of(tilemap: &Tilemap, area: &[(u32, u32)], wrap: bool) -> &'static str {
+
+    let mut counts = [0; 3];
+
+    for &(x, y) in area.iter() {
+        for (nx, ny) in neighbors(x, y, tilemap.width(), tilemap.height(), wrap).into_iter().chain(Some((x, y))) {
+
+            let tile = tilemap.get(nx, ny);
+
+            if tile.is_water() {
+                continue;
+            }
+
+            match tile.cat.ground() {
+                "sand" => counts[1] += 1,
+                "snow" => counts[2] += 1,
+                _ => counts[0] += 1,
+            }
+        }
+    }
+
+    let most = (0..3).max_by_key(|i| counts[*i]).unwrap();
+
+    ["grass", "sand", "snow"][most]
+}
+
+// Names for everything worth naming, from an rng of their own so naming 
+// doesn't change anything else generated from the seed. Sites come first 
+// in reading order, then areas from largest to smallest by kind.
+pub fn name_world(
+    tilemap: &Tilemap, 
+    river_map: &[Vec<bool>], 
+    lake_map: &[Vec<bool>], 
+    wrap: bool, 
+    config: &NameConfig, 
+    seed: u64
+) -> Vec<Name> {
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed ^ 0x6e61_6d65); // "name"
+    let mut used = HashSet::new();
+    let mut taken = HashSet::new();
+    let mut names = vec![];
+
+    let width = tilemap.width();
+    let height = tilemap.height();
+
+    for y in 0..height {
+        for x in 0..width {
+
+            let tile = tilemap.get(x, y);
+
+            let kind = if tile.dwelling && tile.name.starts_with("castle") {
+                "castle"
+            } else if tile.dwelling {
+                "town"
+            } else if tile.name.starts_with("cave") {
+                "cave"
+            } else {
+                continue;
+            };
+
+            let name = unique_name(kind, style(tile.cat.ground()), &mut used, &mut taken, &mut rng);
+
+            names.push(Name { kind, name, at: (x, y), tiles: 1 });
+        }
+    }
+
+    let water = |x: u32, y: u32| {
+        tilemap.get(x, y).is_water() && !river_map[x as usize][y as usize] && !lake_map[x as usize][y as usize]
+    };
+
+    let kinds = vec![
+        ("landmass", config.min_landmass_tiles, landmasses(tilemap, river_map, wrap).1),
+        ("mountains", config.min_range_tiles, areas(width, height, wrap, |x, y| tilemap.get(x, y).name.starts_with("mountain"))),
+        ("river", config.min_river_tiles, areas(width, height, wrap, |x, y| river_map[x as usize][y as usize])),
+        ("lake", 1, areas(width, height, wrap, |x, y| lake_map[x as usize][y as usize])),
+        ("sea", config.min_sea_tiles, areas(width, height, wrap, water)),
+    ];
+
+    for (kind, min_tiles, mut found) in kinds {
+
+        found.sort_by_key(|area| std::cmp::Reverse(area.len()));
+
+        for area in found.iter().filter(|area| area.len() as u32 >= min_tiles) {
+
+            let kind = if kind == "sea" && area.len() as u32 >= config.ocean_tiles { "ocean" } else { kind };
+
+            // Rivers are labeled halfway along instead of at their average 
+            // position, which may be off the river where it bends
+            let at = if kind == "river" { halfway(area, width, height, wrap) } else { middle(area) };
+
+            let name = unique_name(kind, style(ground_of(tilemap, area, wrap)), &mut used, &mut taken, &mut rng);
+
+            names.push(Name { kind, name, at, tiles: area.len() as u32 });
+        }
+    }
+
+    names
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::tile::Tilelist;
+    use std::sync::Arc;
+
+    // An island crowded with sites, with a mountain range and a river, so 
+    // every kind of name is given out many times
+    fn crowded_island() -> (Tilemap, Vec<Vec<bool>>, Vec<Vec<bool>>) {
+
+        let size = 48;
+        let mut tilemap = Tilemap::new(size, size, "water_0000", &Arc::new(Tilelist::old_school()));
+        let mut river_map = vec![vec![false; size as usize]; size as usize];
+        let lake_map = vec![vec![false; size as usize]; size as usize];
+
+        for x in 4..size - 4 {
+            for y in 4..size - 4 {
+
+                let name = match (x % 4, y % 4) {
+                    _ if x == 24 => "water_0000",
+                    (0, 0) if x < 24 => "town_grass",
+                    (0, 0) => "castle_sand",
+                    (2, 2) if y < 12 => "cave_snow",
+                    _ if y < 10 && x > 26 => "mountain_grass",
+                    _ => "grass",
+                };
+
+                tilemap.set_by_name(x, y, name);
+                river_map[x as usize][y as usize] = x == 24;
+            }
+        }
+
+        (tilemap, river_map, lake_map)
+    }
+
+    // The river down the middle runs through one island, it doesn't make two
+    #[test]
+    fn river_split_island_is_one_landmass() {
+
+        let (tilemap, river_map, lake_map) = crowded_island();
+
+        let names = name_world(&tilemap, &river_map, &lake_map, false, &NameConfig::default(), 3);
+
+        assert_eq!(names.iter().filter(|n| n.kind == "landmass").count(), 1);
+        assert_eq!(names.iter().filter(|n| n.kind == "river").count(), 1);
+    }
+
+    #[test]
+    fn names_unique_and_same_for_same_seed() {
+
+        let (tilemap, river_map, lake_map) = crowded_island();
+        let config = NameConfig::default();
+
+        let names = name_world(&tilemap, &river_map, &lake_map, false, &config, 3);
+        let again = name_world(&tilemap, &river_map, &lake_map, false, &config, 3);
+
+        let distinct: HashSet<&str> = names.iter().map(|n| n.name.as_str()).collect();
+
+        assert!(names.len() > 100);
+        assert_eq!(distinct.len(), names.len());
+
+        assert_eq!(names.len(), again.len());
+
+        for (a, b) in names.iter().zip(again.iter()) {
+            assert_eq!((a.kind, &a.name, a.at), (b.kind, &b.name, b.at));
+        }
+    }
+}
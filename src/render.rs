@@ -0,0 +1,339 @@
+use terr::heightmap::Heightmap;
+
+use image::{ImageBuffer, RgbImage, ColorType}; // Writing PNG
+use image::png::PngEncoder;
+use std::fs; // Filesystem
+use std::path::Path;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*; // Parallel rendering
+
+use crate::{TILE_SIZE, TILES_WIDE_SPRITE_SHEET, HEIGHTMAP_RANGE};
+use crate::error::Error;
+use crate::font::{glyph, GLYPH_WIDTH, GLYPH_HEIGHT};
+use crate::kingdoms::Kingdoms;
+use crate::names::Name;
+use crate::tile::{Tilemap, Tilelist};
+
+pub fn test_png_hm(heightmap: &Heightmap<f32>, width: u32, height: u32, dir: &Path, filename: &str) -> Result<(), Error> {
+
+    let ratio = 256.0 / (HEIGHTMAP_RANGE as f32);
+    let img = ImageBuffer::from_fn(width, height, |x, y| {
+        image::Luma([(heightmap.get(x, y) * ratio).round() as u8])
+    });
+    fs::create_dir_all(dir)?;
+    img.save(dir.join([filename, ".png"].concat()))?;
+
+    Ok(())
+}
+
+pub fn test_png_vec(v: &[Vec<bool>], width: u32, height: u32, dir: &Path, filename: &str) -> Result<(), Error> {
+
+    let img = ImageBuffer::from_fn(width, height, |x, y| {
+        let cell_val = v[x as usize][y as usize];
+        let luma_val: u8 = if cell_val { 255 } else { 0 };
+        image::Luma([luma_val])
+    });
+    fs::create_dir_all(dir)?;
+    img.save(dir.join([filename, ".png"].concat()))?;
+
+    Ok(())
+}
+
+// Sprite sheets must be 5 tiles wide and have a tile for every id in the 
+// tile list
+pub fn check_sheet(width: u32, height: u32, tilelist: &Tilelist) -> Result<(), Error> {
+
+    let size = TILE_SIZE as u32;
+    let columns = TILES_WIDE_SPRITE_SHEET as u32;
+
+    if width < columns * size {
+        return Err(Error::Tileset(format!(
+            "sprite sheet is {} pixels wide, it needs to fit {} tiles of {} pixels", width, columns, size
+        )));
+    }
+
+    let needed = tilelist.tiles().iter().map(|tile| tile.id as u32 + 1).max().unwrap_or(0);
+    let available = columns * (height / size);
+
+    if available < needed {
+        return Err(Error::Tileset(format!(
+            "sprite sheet has room for {} tiles, the tile list needs {}", available, needed
+        )));
+    }
+
+    Ok(())
+}
+
+// Sprite sheet sliced into raw RGB tiles once, indexed by tile id
+pub struct TileAtlas {
+    tiles: Vec<Vec<u8>>,
+}
+
+impl TileAtlas {
+
+    pub fn from_path(path: &Path, tilelist: &Tilelist) -> Result<TileAtlas, Error> {
+
+        TileAtlas::from_image(&image::open(path)?.to_rgb8(), tilelist)
+    }
+
+    pub fn from_bytes(bytes: &[u8], tilelist: &Tilelist) -> Result<TileAtlas, Error> {
+
+        TileAtlas::from_image(&image::load_from_memory(bytes)?.to_rgb8(), tilelist)
+    }
+
+    pub fn from_image(sheet: &RgbImage, tilelist: &Tilelist) -> Result<TileAtlas, Error> {
+
+        check_sheet(sheet.width(), sheet.height(), tilelist)?;
+
+        let size = TILE_SIZE as u32;
+        let columns = TILES_WIDE_SPRITE_SHEET as u32;
+        let rows = sheet.height() / size;
+
+        let mut tiles = vec![];
+
+        for id in 0..(columns * rows) {
+
+            let crop_x = (id % columns) * size;
+            let crop_y = (id / columns) * size;
+
+            let mut tile = Vec::with_capacity((size * size * 3) as usize);
+
+            for y in crop_y..crop_y + size {
+                for x in crop_x..crop_x + size {
+                    tile.extend_from_slice(&sheet.get_pixel(x, y).0);
+                }
+            }
+            tiles.push(tile);
+        }
+
+        Ok(TileAtlas { tiles })
+    }
+
+    pub fn len(&self) -> usize {
+
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+
+        self.tiles.is_empty()
+    }
+}
+
+// Draws the tilemap by copying tile rows straight into the output buffer.
+// With the "parallel" feature each row of tiles is drawn on its own thread.
+pub fn render_tilemap(tilemap: &Tilemap, atlas: &TileAtlas) -> Result<RgbImage, Error> {
+
+    let width = tilemap.width();
+    let height = tilemap.height();
+    let size = TILE_SIZE as usize;
+
+    for y in 0..height {
+        for x in 0..width {
+            if tilemap.id(x, y) as usize >= atlas.len() {
+                return Err(Error::Tileset(format!(
+                    "tile id {} at ({}, {}) is outside the sprite sheet", tilemap.id(x, y), x, y
+                )));
+            }
+        }
+    }
+
+    let img_width = width as usize * size;
+    let tile_row_bytes = img_width * size * 3;
+
+    let mut buffer = vec![0_u8; tile_row_bytes * height as usize];
+
+    let draw_tile_row = |(tile_y, chunk): (usize, &mut [u8])| {
+        for row in 0..size {
+            let row_start = row * img_width * 3;
+            for tile_x in 0..width as usize {
+                let tile = &atlas.tiles[tilemap.id(tile_x as u32, tile_y as u32) as usize];
+                let dest = row_start + tile_x * size * 3;
+                chunk[dest..dest + size * 3].copy_from_slice(&tile[row * size * 3..(row + 1) * size * 3]);
+            }
+        }
+    };
+
+    #[cfg(feature = "parallel")]
+    buffer.par_chunks_mut(tile_row_bytes).enumerate().for_each(draw_tile_row);
+
+    #[cfg(not(feature = "parallel"))]
+    buffer.chunks_mut(tile_row_bytes).enumerate().for_each(draw_tile_row);
+
+    Ok(ImageBuffer::from_raw(img_width as u32, height * TILE_SIZE as u32, buffer).unwrap())
+}
+
+pub fn map_png(tilemap: &Tilemap, seed: u64, tileset: &Path, path: &Path) -> Result<(), Error> {
+
+    let img = render_tilemap(tilemap, &TileAtlas::from_path(tileset, tilemap.tilelist())?)?;
+
+    save_png(&img, seed, path)
+}
+
+// Saves PNG with the world seed embedded as a "Seed" tEXt chunk, so any 
+// rendered map can be traced back to the seed that made it
+pub fn save_png(img: &RgbImage, seed: u64, path: &Path) -> Result<(), Error> {
+
+    let mut bytes = vec![];
+    PngEncoder::new(&mut bytes).encode(img, img.width(), img.height(), ColorType::Rgb8)?;
+
+    let mut chunk_data = b"tEXt".to_vec();
+    chunk_data.extend_from_slice(b"Seed\0");
+    chunk_data.extend_from_slice(seed.to_string().as_bytes());
+
+    let mut chunk = ((chunk_data.len() - 4) as u32).to_be_bytes().to_vec();
+    chunk.extend_from_slice(&chunk_data);
+    chunk.extend_from_slice(&crc32fast::hash(&chunk_data).to_be_bytes());
+
+    // 8 byte signature followed by the 25 byte IHDR chunk, which must come first
+    let ihdr_end = 33;
+    bytes.splice(ihdr_end..ihdr_end, chunk);
+
+    fs::write(path, bytes)?;
+
+    Ok(())
+}
+
+// Writes names onto a rendered map in white capitals with a black outline. 
+// Landmasses and seas get the largest letters, sites the smallest, placed 
+// under the site instead of over it.
+pub fn draw_labels(img: &mut RgbImage, names: &[Name]) {
+
+    let size = TILE_SIZE as i32;
+
+    for name in names.iter() {
+
+        let scale = match name.kind {
+            "landmass" | "sea" | "ocean" => 3,
+            "mountains" | "river" | "lake" => 2,
+            _ => 1,
+        };
+
+        let advance = (GLYPH_WIDTH as i32 + 1) * scale;
+        let text_width = name.name.chars().count() as i32 * advance - scale;
+        let text_height = GLYPH_HEIGHT as i32 * scale;
+
+        let center_x = name.at.0 as i32 * size + size / 2;
+        let top = if scale == 1 {
+            (name.at.1 as i32 + 1) * size + 1
+        } else {
+            name.at.1 as i32 * size + size / 2 - text_height / 2
+        };
+        let left = center_x - text_width / 2;
+
+        // Outline first, then the letters over it
+        for &(outline, color) in [(true, [0, 0, 0]), (false, [255, 255, 255])].iter() {
+            for (i, c) in name.name.chars().enumerate() {
+
+                let rows = glyph(c);
+
+                for (gy, row) in rows.iter().enumerate() {
+                    for gx in 0..GLYPH_WIDTH as i32 {
+
+                        if row & (1 << (GLYPH_WIDTH as i32 - 1 - gx)) == 0 {
+                            continue;
+                        }
+
+                        let px = left + i as i32 * advance + gx * scale;
+                        let py = top + gy as i32 * scale;
+                        let pad = if outline { 1 } else { 0 };
+
+                        for y in py - pad..py + scale + pad {
+                            for x in px - pad..px + scale + pad {
+                                if x >= 0 && y >= 0 && (x as u32) < img.width() && (y as u32) < img.height() {
+                                    img.put_pixel(x as u32, y as u32, image::Rgb(color));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Color of a kingdom, hues spread by the golden angle so neighbors rarely 
+// look alike however many kingdoms there are
+fn kingdom_color(kingdom: u32) -> [f32; 3] {
+
+    let hue = (kingdom as f32 * 137.508) % 360.0 / 60.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+
+    let (r, g, b) = match hue as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+
+    [r * 255.0, g * 255.0, b * 255.0]
+}
+
+// Tints a rendered map with the color of the kingdom each tile belongs to 
+// and draws borders where kingdoms meet, in black on the tile edges. 
+// Capitals are framed in their kingdom's color.
+pub fn draw_kingdoms(img: &mut RgbImage, kingdoms: &Kingdoms, tint: f32) {
+
+    let size = TILE_SIZE as u32;
+    let width = kingdoms.map.len() as u32;
+    let height = kingdoms.map.first().map_or(0, |column| column.len() as u32);
+
+    let owner = |x: i64, y: i64| {
+        if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+            None
+        } else {
+            kingdoms.map[x as usize][y as usize]
+        }
+    };
+
+    for ty in 0..height {
+        for tx in 0..width {
+
+            let kingdom = match owner(tx as i64, ty as i64) {
+                Some(kingdom) => kingdom,
+                None => continue,
+            };
+
+            let color = kingdom_color(kingdom);
+
+            // Sides of the tile that face another kingdom: up, right, down, left
+            let border: Vec<bool> = [(0, -1), (1, 0), (0, 1), (-1, 0)].iter()
+                .map(|&(dx, dy)| matches!(owner(tx as i64 + dx, ty as i64 + dy), Some(other) if other != kingdom))
+                .collect();
+
+            for py in 0..size {
+                for px in 0..size {
+
+                    let pixel = img.get_pixel_mut(tx * size + px, ty * size + py);
+
+                    let edge = (border[0] && py == 0) || (border[1] && px == size - 1)
+                        || (border[2] && py == size - 1) || (border[3] && px == 0);
+
+                    if edge {
+                        *pixel = image::Rgb([0, 0, 0]);
+                        continue;
+                    }
+
+                    for (channel, c) in pixel.0.iter_mut().zip(color.iter()) {
+                        *channel = (*channel as f32 * (1.0 - tint) + c * tint).round() as u8;
+                    }
+                }
+            }
+        }
+    }
+
+    for (i, kingdom) in kingdoms.kingdoms.iter().enumerate() {
+
+        let color = kingdom_color(i as u32);
+        let (cx, cy) = kingdom.capital;
+
+        for p in 0..size {
+            for &(px, py) in [(p, 0), (p, size - 1), (0, p), (size - 1, p), (p, 1), (p, size - 2), (1, p), (size - 2, p)].iter() {
+                img.put_pixel(cx * size + px, cy * size + py, image::Rgb([color[0] as u8, color[1] as u8, color[2] as u8]));
+            }
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use terr::heightmap::Heightmap;
+
+use rand::prelude::*; // Random
+use std::collections::HashMap; // For associative array-like data structures
+
+use crate::config::TerrainConfig;
+use crate::grid::{neighbor_coor, neighbors, dist_to_water};
+use crate::heightmap::fill_depressions;
+use crate::tile::{Tilemap, TileCategory};
+
+// Generate river starting points, none if no land is high enough
+pub fn pick_river_starts<R: Rng>(
+    heightmap: &Heightmap<f32>, 
+    width: u32, 
+    height: u32, 
+    terrain: &TerrainConfig, 
+    rng: &mut R
+) -> Vec<(u32, u32)> {
+
+    let mut available_river_starts = vec![];
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let h_val = heightmap.get(x, y);
+
+            if h_val > terrain.cutoff_terrain - 10.0 {
+                available_river_starts.push((x, y));
+            }
+        }
+    }
+
+    if available_river_starts.is_empty() {
+        return vec![];
+    }
+
+    let num_river_starts: u32 = (available_river_starts.len() as f32 / (width * height) as f32 * 100.0).ceil() as u32;
+
+    let mut river_starts = vec![];
+
+    for _ in 0..num_river_starts {
+
+        let river_start = available_river_starts[rng.gen_range(0, available_river_starts.len())];
+
+        river_starts.push(river_start);
+    }
+
+    river_starts
+}
+
+// Draw each river, returning a binary map of river placement
+pub fn draw_rivers<R: Rng>(
+    tilemap: &mut Tilemap, 
+    river_starts: &[(u32, u32)], 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    rng: &mut R
+) -> Vec<Vec<bool>> {
+
+    let mut river_map_all = vec![vec![false; height as usize]; width as usize];
+
+    let flow_options = ["up","down","left","right"];
+
+    for river_start in river_starts.iter() {
+
+        let mut river_map = vec![vec![false; height as usize]; width as usize];
+
+        let start_x = river_start.0;
+        let start_y = river_start.1;
+
+        river_map[start_x as usize][start_y as usize] = true;
+
+        let tile = tilemap.get(start_x, start_y);
+
+        // Skip this river start if already under water
+        if tile.cat == TileCategory::Water {
+            continue;
+        }
+
+        // Determine flow direction by finding water distance for each direction
+        // Then random chance go to closest or random direction
+
+        let mut flowing = true;
+        let mut flow_count = 0;
+        let mut flow_dir;
+
+        let mut current_x = start_x;
+        let mut current_y = start_y;
+
+        while flowing {
+
+            let mut water_dist: HashMap<String, u32> = HashMap::new();
+
+            water_dist.insert(String::from("up"), dist_to_water(tilemap, current_x, current_y, width, height, wrap, "up"));
+            water_dist.insert(String::from("down"), dist_to_water(tilemap, current_x, current_y, width, height, wrap, "down"));
+            water_dist.insert(String::from("left"), dist_to_water(tilemap, current_x, current_y, width, height, wrap, "left"));
+            water_dist.insert(String::from("right"), dist_to_water(tilemap, current_x, current_y, width, height, wrap, "right"));
+
+            let mut flow_data: HashMap<String, (u32, u32)> = HashMap::new();
+
+            flow_data.insert(String::from("up"), neighbor_coor(current_x as i32, current_y as i32, width, height, wrap, "up"));
+            flow_data.insert(String::from("down"), neighbor_coor(current_x as i32, current_y as i32, width, height, wrap, "down"));
+            flow_data.insert(String::from("left"), neighbor_coor(current_x as i32, current_y as i32, width, height, wrap, "left"));
+            flow_data.insert(String::from("right"), neighbor_coor(current_x as i32, current_y as i32, width, height, wrap, "right"));
+
+            flow_dir = "up";
+
+            if water_dist.get("down") < water_dist.get(flow_dir) {
+                flow_dir = "down";
+            }
+            if water_dist.get("left") < water_dist.get(flow_dir) {
+                flow_dir = "left";
+            }
+            if water_dist.get("right") < water_dist.get(flow_dir) {
+                flow_dir = "right";
+            }
+
+            let mut flow_coor = flow_data.get(flow_dir).unwrap();
+            let r_num = rng.gen_range(1, 1001);
+
+            if river_map[flow_coor.0 as usize][flow_coor.1 as usize] || r_num < 500 {
+
+                let r_num2 = rng.gen_range(0, 4);
+
+                flow_dir = flow_options[r_num2];
+                flow_coor = flow_data.get(flow_dir).unwrap();
+            }
+
+            let flow_x = flow_coor.0;
+            let flow_y = flow_coor.1;
+
+            let flow_tile = tilemap.get(flow_x, flow_y);
+
+            // If over water, or prev spot, draw and stop
+            if flow_tile.cat == TileCategory::Water || flow_count > 2500 {
+
+                for y in 0..height {
+                    for x in 0..width {
+
+                        if river_map[x as usize][y as usize] {
+                            tilemap.set_by_name(x, y, "water_0000");
+                        }
+                    }
+                }
+
+                flowing = false;
+                flow_count = 0;
+            }
+
+            if !river_map[flow_x as usize][flow_y as usize] {
+                
+                river_map[flow_x as usize][flow_y as usize] = true;
+                river_map_all[flow_x as usize][flow_y as usize] = true;
+                
+                current_x = flow_x;
+                current_y = flow_y;
+            }
+
+            flow_count += 1;
+        }
+    }
+
+    river_map_all
+}
+
+// Draw each river by following the steepest descent of the heightmap, 
+// returning a binary map of river placement. With pits filled every river 
+// reaches the sea or joins another river, otherwise a river that gets stuck 
+// in a pit ends in a small lake there.
+pub fn draw_rivers_downhill(
+    tilemap: &mut Tilemap, 
+    heightmap: &Heightmap<f32>, 
+    river_starts: &[(u32, u32)], 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    terrain: &TerrainConfig, 
+    fill_pits: bool
+) -> Vec<Vec<bool>> {
+
+    let filled;
+    let surface = if fill_pits {
+        filled = fill_depressions(heightmap, width, height, wrap, terrain.cutoff_water);
+        &filled
+    } else {
+        heightmap
+    };
+
+    let mut river_map_all = vec![vec![false; height as usize]; width as usize];
+
+    for &(start_x, start_y) in river_starts.iter() {
+
+        // Skip this river start if already under water
+        if tilemap.get(start_x, start_y).cat == TileCategory::Water {
+            continue;
+        }
+
+        for (x, y) in downhill_course(tilemap, surface, (start_x, start_y), width, height, wrap) {
+            tilemap.set_by_name(x, y, "water_0000");
+            river_map_all[x as usize][y as usize] = true;
+        }
+    }
+
+    river_map_all
+}
+
+// Cells a river starting at "start" flows through, following the steepest 
+// descent of "surface" until it reaches water. A river stuck in a pit ends 
+// in a small lake around it.
+pub fn downhill_course(
+    tilemap: &Tilemap, 
+    surface: &Heightmap<f32>, 
+    start: (u32, u32), 
+    width: u32, 
+    height: u32, 
+    wrap: bool
+) -> Vec<(u32, u32)> {
+
+    let mut course = vec![start];
+    let mut current = start;
+
+    loop {
+
+        let lowest = neighbors(current.0, current.1, width, height, wrap).into_iter()
+            .min_by(|a, b| surface.get(a.0, a.1).partial_cmp(&surface.get(b.0, b.1)).unwrap())
+            .unwrap();
+
+        if surface.get(lowest.0, lowest.1) >= surface.get(current.0, current.1) {
+            course.extend(pit_lake(surface, current, width, height, wrap));
+            break;
+        }
+
+        current = lowest;
+
+        // Reached the sea, a lake or another river
+        if tilemap.get(current.0, current.1).cat == TileCategory::Water {
+            break;
+        }
+
+        course.push(current);
+    }
+
+    course
+}
+
+// Cells around a pit that fill with water before it overflows, kept small
+fn pit_lake(surface: &Heightmap<f32>, pit: (u32, u32), width: u32, height: u32, wrap: bool) -> Vec<(u32, u32)> {
+
+    let max_cells = 25;
+    let max_h = surface.get(pit.0, pit.1) + 1.0;
+
+    let mut lake = vec![pit];
+    let mut next = 0;
+
+    while next < lake.len() && lake.len() < max_cells {
+
+        let (x, y) = lake[next];
+        next += 1;
+
+        for n in neighbors(x, y, width, height, wrap) {
+            if lake.len() < max_cells && !lake.contains(&n) && surface.get(n.0, n.1) <= max_h {
+                lake.push(n);
+            }
+        }
+    }
+
+    lake
+}
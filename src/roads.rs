@@ -0,0 +1,585 @@
+use std::collections::BinaryHeap; // Open set of the path search
+
+use crate::config::RoadConfig;
+use crate::grid::{axis_dist, offset};
+use crate::heightmap::FloodCell;
+use crate::tile::{Tile, Tilemap, TileCategory};
+
+// Order is important: up, right, down, left
+pub const DIRECTIONS: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+fn is_road(tile: Tile) -> bool {
+
+    tile.name.starts_with("road")
+}
+
+// Cost of walking onto a tile, None where roads can't go
+fn step_cost(tile: Tile, road: &RoadConfig) -> Option<f32> {
+
+    if !tile.walkable || tile.name.starts_with("cave") {
+        None
+    } else if tile.bridge || is_road(tile) {
+        Some(road.road_cost)
+    } else if tile.name.starts_with("hill") {
+        Some(road.hill_cost)
+    } else if tile.name.ends_with("forest") {
+        Some(road.forest_cost)
+    } else {
+        match tile.cat {
+            TileCategory::Swamp => Some(road.swamp_cost),
+            TileCategory::Sand => Some(road.sand_cost),
+            TileCategory::Snow | TileCategory::Ice => Some(road.snow_cost),
+            _ => Some(1.0),
+        }
+    }
+}
+
+// One step of a road from "current" in direction (dx, dy). A river is 
+// crossed in a straight line, landing on the first tile past it, so bridges 
+// never turn. Returns where the step ends and what it costs.
+fn road_step(
+    tilemap: &Tilemap, 
+    river_map: &[Vec<bool>], 
+    current: (u32, u32), 
+    direction: (i32, i32), 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    road: &RoadConfig, 
+    bridges: bool
+) -> Option<((u32, u32), f32)> {
+
+    let mut bridge_length = 0;
+    let mut cost = 0.0;
+
+    let landing = loop {
+
+        let (x, y) = offset(current.0, current.1, direction, bridge_length + 1, width, height, wrap)?;
+
+        if !river_map[x as usize][y as usize] {
+            break (x, y);
+        }
+
+        let tile = tilemap.get(x, y);
+
+        if tile.bridge {
+
+            // Existing bridges can only be crossed the way they were built
+            if (tile.name == "bridge_up_down") != (direction.1 != 0) {
+                return None;
+            }
+            cost += road.road_cost;
+
+        } else if bridges && tile.cat == TileCategory::Water {
+            cost += road.bridge_cost;
+        } else {
+            return None;
+        }
+
+        bridge_length += 1;
+
+        if bridge_length > road.max_bridge_length as i32 {
+            return None;
+        }
+    };
+
+    step_cost(tilemap.get(landing.0, landing.1), road).map(|step| (landing, cost + step))
+}
+
+// Cheapest road between two tiles with A*, and its cost
+fn find_road(
+    tilemap: &Tilemap, 
+    river_map: &[Vec<bool>], 
+    from: (u32, u32), 
+    to: (u32, u32), 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    road: &RoadConfig, 
+    bridges: bool
+) -> Option<(Vec<(u32, u32)>, f32)> {
+
+    let index = |(x, y): (u32, u32)| (y * width + x) as usize;
+
+    // No step is cheaper than an existing road, so this never overestimates
+    let estimate = |from: (u32, u32)| {
+        let (dx, dy) = axis_dist(from, to, width, height, wrap);
+        (dx + dy) as f32 * road.road_cost
+    };
+
+    let mut cost = vec![f32::INFINITY; (width * height) as usize];
+    let mut came_from = vec![usize::MAX; (width * height) as usize];
+    let mut closed = vec![false; (width * height) as usize];
+    let mut open = BinaryHeap::new();
+
+    cost[index(from)] = 0.0;
+    open.push(FloodCell { h: estimate(from), x: from.0, y: from.1 });
+
+    while let Some(cell) = open.pop() {
+
+        let current = (cell.x, cell.y);
+
+        if current == to {
+            return Some((trace_road(&came_from, to, width, height, wrap), cost[index(to)]));
+        }
+
+        if closed[index(current)] {
+            continue;
+        }
+        closed[index(current)] = true;
+
+        for &direction in DIRECTIONS.iter() {
+
+            if let Some((next, step)) = road_step(tilemap, river_map, current, direction, width, height, wrap, road, bridges) {
+
+                let new_cost = cost[index(current)] + step;
+
+                if new_cost < cost[index(next)] {
+                    cost[index(next)] = new_cost;
+                    came_from[index(next)] = index(current);
+                    open.push(FloodCell { h: new_cost + estimate(next), x: next.0, y: next.1 });
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// Walk back from the end of a path found with "came_from", filling in 
+// the tiles of bridges
+pub fn trace_road(came_from: &[usize], to: (u32, u32), width: u32, height: u32, wrap: bool) -> Vec<(u32, u32)> {
+
+    let mut path = vec![to];
+    let mut current = to;
+
+    while came_from[(current.1 * width + current.0) as usize] != usize::MAX {
+
+        let i = came_from[(current.1 * width + current.0) as usize];
+        let prev = (i as u32 % width, i as u32 / width);
+
+        let mut dx = (prev.0 as i32 - current.0 as i32).signum();
+        let mut dy = (prev.1 as i32 - current.1 as i32).signum();
+
+        // A step across the edge of a wrapping world goes the other way
+        let (dist_x, dist_y) = axis_dist(prev, current, width, height, wrap);
+
+        if dist_x != (prev.0 as i32 - current.0 as i32).unsigned_abs() {
+            dx = -dx;
+        }
+        if dist_y != (prev.1 as i32 - current.1 as i32).unsigned_abs() {
+            dy = -dy;
+        }
+
+        for steps in 1..(dist_x + dist_y) as i32 {
+            path.extend(offset(current.0, current.1, (dx, dy), steps, width, height, wrap));
+        }
+
+        path.push(prev);
+        current = prev;
+    }
+
+    path.reverse();
+    path
+}
+
+fn paint_road(
+    tilemap: &mut Tilemap, 
+    river_map: &[Vec<bool>], 
+    road_map: &mut [Vec<bool>], 
+    path: &[(u32, u32)]
+) {
+
+    for (n, &(x, y)) in path.iter().enumerate() {
+
+        let tile = tilemap.get(x, y);
+
+        road_map[x as usize][y as usize] = true;
+
+        if river_map[x as usize][y as usize] {
+
+            // Bridges are never at either end of a road
+            if !tile.bridge {
+                let bridge = if path[n - 1].0 == x { "bridge_up_down" } else { "bridge_left_right" };
+                tilemap.set_by_name(x, y, bridge);
+            }
+
+        } else if !(tile.dwelling || tile.bridge || is_road(tile)) {
+
+            if tile.cat == TileCategory::Sand {
+                tilemap.set_by_name(x, y, "road_sand_0000");
+            } else if tile.cat == TileCategory::Snow || tile.cat == TileCategory::Ice {
+                tilemap.set_by_name(x, y, "road_snow_0000");
+            } else {
+                tilemap.set_by_name(x, y, "road_0000");
+            }
+        }
+    }
+}
+
+// Pick the road tile matching the way each road continues
+fn connect_roads(tilemap: &mut Tilemap, road_map: &[Vec<bool>], width: u32, height: u32, wrap: bool) {
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let tile = tilemap.get(x, y);
+
+            if !road_map[x as usize][y as usize] || !is_road(tile) {
+                continue;
+            }
+
+            let mut t_str = String::from(match tile.cat {
+                TileCategory::Sand => "road_sand_",
+                TileCategory::Snow => "road_snow_",
+                _ => "road_",
+            });
+
+            for &(dx, dy) in DIRECTIONS.iter() {
+
+                let connected = offset(x, y, (dx, dy), 1, width, height, wrap).is_some_and(|(nx, ny)| {
+
+                    let n_tile = tilemap.get(nx, ny);
+
+                    is_road(n_tile) || n_tile.dwelling ||
+                        (n_tile.bridge && (n_tile.name == "bridge_up_down") == (dy != 0))
+                });
+
+                t_str.push(if connected { '1' } else { '0' });
+            }
+
+            tilemap.set_by_name(x, y, &t_str);
+        }
+    }
+}
+
+fn find_group(group: &mut [usize], i: usize) -> usize {
+
+    let mut root = i;
+
+    while group[root] != root {
+        root = group[root];
+    }
+
+    group[i] = root;
+
+    root
+}
+
+// Cost of travelling between two sites over the roads chosen so far
+fn network_cost(connections: &[(usize, usize, f32)], from: usize, to: usize, num_sites: usize) -> f32 {
+
+    let mut cost = vec![f32::INFINITY; num_sites];
+    let mut done = vec![false; num_sites];
+
+    cost[from] = 0.0;
+
+    loop {
+
+        let next = (0..num_sites)
+            .filter(|&i| !done[i] && cost[i].is_finite())
+            .min_by(|&a, &b| cost[a].partial_cmp(&cost[b]).unwrap());
+
+        let current = match next {
+            Some(i) if i != to => i,
+            _ => break,
+        };
+
+        done[current] = true;
+
+        for &(a, b, c) in connections.iter() {
+
+            let other = if a == current { b } else if b == current { a } else { continue };
+
+            if cost[current] + c < cost[other] {
+                cost[other] = cost[current] + c;
+            }
+        }
+    }
+
+    cost[to]
+}
+
+// Which sites roads can join, found with the same steps the road search 
+// takes. Sites with the same number can be joined, the number being the 
+// first site of the area.
+fn road_areas(
+    tilemap: &Tilemap, 
+    river_map: &[Vec<bool>], 
+    sites: &[(u32, u32)], 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    road: &RoadConfig, 
+    bridges: bool
+) -> Vec<usize> {
+
+    let index = |(x, y): (u32, u32)| (y * width + x) as usize;
+
+    let mut area = vec![usize::MAX; (width * height) as usize];
+    let mut site_areas = vec![];
+
+    for (i, &site) in sites.iter().enumerate() {
+
+        if area[index(site)] == usize::MAX {
+
+            let mut fill = vec![site];
+            area[index(site)] = i;
+
+            while let Some(current) = fill.pop() {
+                for &direction in DIRECTIONS.iter() {
+                    if let Some((next, _)) = road_step(tilemap, river_map, current, direction, width, height, wrap, road, bridges) {
+                        if area[index(next)] == usize::MAX {
+                            area[index(next)] = i;
+                            fill.push(next);
+                        }
+                    }
+                }
+            }
+        }
+
+        site_areas.push(area[index(site)]);
+    }
+
+    site_areas
+}
+
+// Connect every town and castle by road, returning a binary map of road 
+// placement. The network is a minimum spanning tree by road cost between 
+// all sites roads can join, plus extra roads to nearby sites where going 
+// around through the network is much longer. Bridges are built where roads 
+// cross rivers.
+pub fn build_roads(
+    tilemap: &mut Tilemap, 
+    river_map: &[Vec<bool>], 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    road: &RoadConfig, 
+    bridges: bool
+) -> Vec<Vec<bool>> {
+
+    let mut road_map = vec![vec![false; height as usize]; width as usize];
+
+    let mut sites = vec![];
+
+    for x in 0..width {
+        for y in 0..height {
+            if tilemap.get(x, y).dwelling {
+                sites.push((x, y));
+            }
+        }
+    }
+
+    let dist = |i: usize, j: usize| {
+        let (dx, dy) = axis_dist(sites[i], sites[j], width, height, wrap);
+        dx as u64 * dx as u64 + dy as u64 * dy as u64
+    };
+
+    let area = road_areas(tilemap, river_map, &sites, width, height, wrap, road, bridges);
+
+    // Roads from every site to its nearest neighbors, searched once for 
+    // both the tree and the extra roads
+    let mut candidates = vec![];
+
+    for i in 0..sites.len() {
+
+        let mut nearest: Vec<usize> = (0..sites.len()).filter(|&j| j != i && area[j] == area[i]).collect();
+        nearest.sort_by_key(|&j| (dist(i, j), j));
+
+        for &j in nearest.iter().take(road.neighbors as usize) {
+
+            let pair = (i.min(j), i.max(j));
+
+            if !candidates.contains(&pair) {
+                candidates.push(pair);
+            }
+        }
+    }
+
+    candidates.sort_by_key(|&(i, j)| (dist(i, j), i, j));
+
+    let mut found: Vec<(usize, usize, Vec<(u32, u32)>, f32)> = candidates.iter()
+        .filter_map(|&(i, j)| {
+            find_road(tilemap, river_map, sites[i], sites[j], width, height, wrap, road, bridges)
+                .map(|(path, cost)| (i, j, path, cost))
+        })
+        .collect();
+
+    // Minimum spanning tree by road cost, cheapest first, so an easy road 
+    // along a valley wins over a short one over hills. The sort is stable, 
+    // roads costing the same stay nearest first.
+    found.sort_by(|a, b| a.3.partial_cmp(&b.3).unwrap());
+
+    let mut group: Vec<usize> = (0..sites.len()).collect();
+    let mut connections = vec![];
+    let mut paths = vec![];
+    let mut extras = vec![];
+
+    for (i, j, path, cost) in found {
+
+        let group_i = find_group(&mut group, i);
+        let group_j = find_group(&mut group, j);
+
+        if group_i != group_j {
+            group[group_i] = group_j;
+            connections.push((i, j, cost));
+            paths.push(path);
+        } else {
+            extras.push((i, j, path, cost));
+        }
+    }
+
+    // Parts of the tree the nearest neighbors didn't join, closest pair 
+    // first. Every pair in an area can be joined, so each road searched for 
+    // is one the tree keeps.
+    let mut pairs = vec![];
+
+    for i in 0..sites.len() {
+        for j in (i + 1)..sites.len() {
+            if area[i] == area[j] {
+                pairs.push((i, j));
+            }
+        }
+    }
+
+    pairs.sort_by_key(|&(i, j)| (dist(i, j), i, j));
+
+    for &(i, j) in pairs.iter() {
+
+        let group_i = find_group(&mut group, i);
+        let group_j = find_group(&mut group, j);
+
+        if group_i != group_j {
+            if let Some((path, cost)) = find_road(tilemap, river_map, sites[i], sites[j], width, height, wrap, road, bridges) {
+                group[group_i] = group_j;
+                connections.push((i, j, cost));
+                paths.push(path);
+            }
+        }
+    }
+
+    // Extra roads between nearest neighbors where going around through the 
+    // network is much longer, nearest first
+    extras.sort_by_key(|e| (dist(e.0, e.1), e.0, e.1));
+
+    for (i, j, path, cost) in extras {
+
+        let around = network_cost(&connections, i, j, sites.len());
+
+        if around > road.detour_factor * cost {
+            connections.push((i, j, cost));
+            paths.push(path);
+        }
+    }
+
+    for path in paths.iter() {
+
+        // A bridge built for an earlier road may cross this one's river the 
+        // other way, then it has to find another way over
+        let blocked = path.iter().enumerate().skip(1).any(|(n, &(x, y))| {
+            let tile = tilemap.get(x, y);
+            tile.bridge && (tile.name == "bridge_up_down") != (path[n - 1].0 == x)
+        });
+
+        if !blocked {
+            paint_road(tilemap, river_map, &mut road_map, path);
+        } else if let Some((path, _)) = find_road(tilemap, river_map, path[0], path[path.len() - 1], width, height, wrap, road, bridges) {
+            paint_road(tilemap, river_map, &mut road_map, &path);
+        }
+    }
+
+    connect_roads(tilemap, &road_map, width, height, wrap);
+
+    road_map
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::tile::Tilelist;
+    use std::sync::Arc;
+
+    // Two towns and a castle with a bay between them and a river past it
+    #[test]
+    fn roads_join_dwellings_and_only_bridge_rivers() {
+
+        let (width, height) = (20, 9);
+        let mut tilemap = Tilemap::new(width, height, "grass", &Arc::new(Tilelist::old_school()));
+        let mut river_map = vec![vec![false; height as usize]; width as usize];
+
+        for y in 0..height {
+            tilemap.set_by_name(12, y, "water_0000");
+            river_map[12][y as usize] = true;
+        }
+        for x in 6..9 {
+            for y in 2..height {
+                tilemap.set_by_name(x, y, "water_0000");
+            }
+        }
+
+        tilemap.set_by_name(2, 5, "town_grass");
+        tilemap.set_by_name(10, 6, "castle_grass");
+        tilemap.set_by_name(17, 4, "town_grass");
+
+        let road_map = build_roads(&mut tilemap, &river_map, width, height, false, &RoadConfig::default(), true);
+
+        for x in 0..width {
+            for y in 0..height {
+
+                if !road_map[x as usize][y as usize] {
+                    continue;
+                }
+
+                let tile = tilemap.get(x, y);
+
+                assert!(tile.dwelling || tile.bridge || is_road(tile), "{} on a road at {:?}", tile.name, (x, y));
+                assert!(!tile.bridge || river_map[x as usize][y as usize], "bridge off the river at {:?}", (x, y));
+
+                // Only dwellings end a road
+                let next = DIRECTIONS.iter()
+                    .filter_map(|&d| offset(x, y, d, 1, width, height, false))
+                    .filter(|&(nx, ny)| road_map[nx as usize][ny as usize])
+                    .count();
+
+                assert!(next >= 2 || tile.dwelling, "road ends at {:?}", (x, y));
+            }
+        }
+
+        for &site in [(2, 5), (10, 6), (17, 4)].iter() {
+            assert!(road_map[site.0 as usize][site.1 as usize], "no road to {:?}", site);
+        }
+    }
+
+    // The two towns nearest each other have a wall of hills between them, 
+    // the tree goes around through the third one instead
+    #[test]
+    fn tree_follows_road_cost_not_distance() {
+
+        let (width, height) = (9, 9);
+        let mut tilemap = Tilemap::new(width, height, "grass", &Arc::new(Tilelist::old_school()));
+        let river_map = vec![vec![false; height as usize]; width as usize];
+
+        for y in 0..7 {
+            tilemap.set_by_name(4, y, "hill_grass");
+        }
+
+        tilemap.set_by_name(0, 0, "town_grass");
+        tilemap.set_by_name(8, 0, "town_grass");
+        tilemap.set_by_name(4, 8, "town_grass");
+
+        // Over the hills is the shortest road between the first two, but 
+        // dearer than either road to the third. No extra roads.
+        let road = RoadConfig { hill_cost: 6.0, detour_factor: 10.0, ..RoadConfig::default() };
+        let road_map = build_roads(&mut tilemap, &river_map, width, height, false, &road, true);
+
+        for y in 0..7 {
+            assert!(!road_map[4][y], "road over the hills at {:?}", (4, y));
+        }
+
+        for &site in [(0, 0), (8, 0), (4, 8)].iter() {
+            assert!(road_map[site.0][site.1], "no road to {:?}", site);
+        }
+    }
+}
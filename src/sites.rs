@@ -0,0 +1,670 @@
+use rand::prelude::*; // Random
+use std::cmp; // For finding minimum
+
+use crate::config::SiteConfig;
+use crate::error::Warning;
+use crate::grid::{axis_dist, landmasses, neighbor_coor, neighbors, offset};
+use crate::tile::{Tile, Tilemap, TileCategory};
+
+// Bridges over rivers, a few for every river and spaced apart. Warns when 
+// there is room for fewer bridges than requested.
+pub fn place_bridges<R: Rng>(
+    tilemap: &mut Tilemap, 
+    river_map_all: &[Vec<bool>], 
+    num_river_starts: usize, 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    sites: &SiteConfig, 
+    rng: &mut R
+) -> Option<Warning> {
+
+    let mut valid_bridge_positions = vec![];
+
+    for x in 0..width {
+        for y in 0..height {
+
+            if river_map_all[x as usize][y as usize] && tilemap.get(x, y).cat == TileCategory::Water {
+
+                let n_up = neighbor_coor(x as i32, y as i32, width, height, wrap, "up");
+                let n_down = neighbor_coor(x as i32, y as i32, width, height, wrap, "down");
+                let n_left = neighbor_coor(x as i32, y as i32, width, height, wrap, "left");
+                let n_right = neighbor_coor(x as i32, y as i32, width, height, wrap, "right");
+
+                let tile_up = tilemap.get(n_up.0, n_up.1);
+                let tile_down = tilemap.get(n_down.0, n_down.1);
+                let tile_left = tilemap.get(n_left.0, n_left.1);
+                let tile_right = tilemap.get(n_right.0, n_right.1);
+
+                if tile_up.walkable && tile_down.walkable {
+                    valid_bridge_positions.push((x, y, "bridge_up_down"));
+                } else if tile_left.walkable && tile_right.walkable {
+                    valid_bridge_positions.push((x, y, "bridge_left_right"));
+                }
+            }
+        }
+    }
+
+    // Sample without replacement, skipping positions too close to a bridge 
+    // that was already picked
+    valid_bridge_positions.shuffle(rng);
+
+    let num_bridges = num_river_starts * sites.bridges_per_river as usize;
+    let spacing = sites.bridge_spacing;
+
+    let mut final_bridge_positions: Vec<(u32, u32, &str)> = vec![];
+
+    for &(x, y, bridge) in valid_bridge_positions.iter() {
+
+        if final_bridge_positions.len() >= num_bridges {
+            break;
+        }
+
+        let too_close = final_bridge_positions.iter().any(|&(bx, by, _)| {
+            let (dx, dy) = axis_dist((bx, by), (x, y), width, height, wrap);
+            dx + dy < spacing
+        });
+
+        if !too_close {
+            final_bridge_positions.push((x, y, bridge));
+        }
+    }
+
+    for &(x, y, bridge) in final_bridge_positions.iter() {
+        tilemap.set_by_name(x, y, bridge);
+    }
+
+    if final_bridge_positions.len() < num_bridges {
+        return Some(Warning::FewerBridges {
+            requested: num_bridges, 
+            placed: final_bridge_positions.len(), 
+            candidates: valid_bridge_positions.len(),
+        });
+    }
+
+    None
+}
+
+// Caves, in mountainsides next to walkable land and in some hills. Warns 
+// when there is nowhere to put one.
+pub fn place_caves<R: Rng>(
+    tilemap: &mut Tilemap, 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    rng: &mut R
+) -> Option<Warning> {
+
+    let mut valid_cave_positions = vec![];
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let mut valid = false;
+
+            let tile = tilemap.get(x, y);
+
+            if tile.name == "mountain_grass" || tile.name == "mountain_sand" || tile.name == "mountain_snow" {
+
+                let n_up = neighbor_coor(x as i32, y as i32, width, height, wrap, "up");
+                let n_down = neighbor_coor(x as i32, y as i32, width, height, wrap, "down");
+                let n_left = neighbor_coor(x as i32, y as i32, width, height, wrap, "left");
+                let n_right = neighbor_coor(x as i32, y as i32, width, height, wrap, "right");
+
+                let tile_up = tilemap.get(n_up.0, n_up.1);
+                let tile_down = tilemap.get(n_down.0, n_down.1);
+                let tile_left = tilemap.get(n_left.0, n_left.1);
+                let tile_right = tilemap.get(n_right.0, n_right.1);
+
+                if tile_up.walkable || tile_down.walkable || tile_left.walkable || tile_right.walkable {
+                    valid = true;
+                }
+
+                if valid_cave_positions.contains(&n_up) || 
+                   valid_cave_positions.contains(&n_down) || 
+                   valid_cave_positions.contains(&n_left) || 
+                   valid_cave_positions.contains(&n_right) {
+
+                    continue;
+               }
+            }
+
+            if tile.name == "hill_grass" || tile.name == "hill_sand" || tile.name == "hill_snow" {
+                let r_num = rng.gen_range(0, 1000);
+                if r_num < 100 {
+                    valid = true;
+                }
+            }
+
+            if valid {
+                valid_cave_positions.push((x, y));
+            }
+        }
+    }
+
+    if valid_cave_positions.is_empty() {
+        return Some(Warning::NoRoom { what: "caves" });
+    }
+
+    let cave_num: u32 = (valid_cave_positions.len() as f32 / ((width + height) as f32 / 2.0 / 15.0)) as u32;
+
+    for _ in 0..cave_num {
+
+        let r_num = rng.gen_range(0, valid_cave_positions.len());
+        let coor = valid_cave_positions[r_num];
+        let tile = tilemap.get(coor.0, coor.1);
+
+        tilemap.set_by_name(coor.0, coor.1, &["cave_", tile.cat.ground()].concat());
+    }
+
+    None
+}
+
+// Open ground a town can go on. Hills, mountains and caves share their 
+// ground's category, but a town on one would flatten it.
+fn town_ground(tile: Tile) -> bool {
+
+    (tile.cat == TileCategory::Grass || tile.cat == TileCategory::Sand || tile.cat == TileCategory::Snow)
+        && tile.walkable && !tile.dwelling
+        && !tile.name.starts_with("mountain") && !tile.name.starts_with("hill") && !tile.name.starts_with("cave")
+}
+
+// Towns / Castles, half of them on the coast if there is room. Warns when 
+// there is no open land for any.
+pub fn place_towns<R: Rng>(
+    tilemap: &mut Tilemap, 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    sites: &SiteConfig, 
+    rng: &mut R
+) -> Option<Warning> {
+
+    let mut valid_town_positions = vec![];
+    let mut valid_town_positions_water = vec![];
+
+    let mut walkable_tiles = 0;
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let mut valid = false;
+
+            let tile = tilemap.get(x, y);
+
+            if tile.walkable {
+                walkable_tiles += 1;
+            }
+
+            let n_up = neighbor_coor(x as i32, y as i32, width, height, wrap, "up");
+            let n_down = neighbor_coor(x as i32, y as i32, width, height, wrap, "down");
+            let n_left = neighbor_coor(x as i32, y as i32, width, height, wrap, "left");
+            let n_right = neighbor_coor(x as i32, y as i32, width, height, wrap, "right");
+
+            let tile_up = tilemap.get(n_up.0, n_up.1);
+            let tile_down = tilemap.get(n_down.0, n_down.1);
+            let tile_left = tilemap.get(n_left.0, n_left.1);
+            let tile_right = tilemap.get(n_right.0, n_right.1);
+
+            if town_ground(tile)
+                && (tile_up.walkable || tile_down.walkable || tile_left.walkable || tile_right.walkable) {
+                    valid = true;
+                }
+
+            if valid {
+
+                if tile_up.cat == TileCategory::Water || tile_down.cat == TileCategory::Water || tile_left.cat == TileCategory::Water || tile_right.cat == TileCategory::Water {
+                    valid_town_positions_water.push((x,y));
+                } else {
+                    valid_town_positions.push((x,y));
+                }
+            }
+        }
+    }
+
+    let town_num = walkable_tiles / sites.walkable_tiles_per_town as usize;
+
+    if town_num > 0 && valid_town_positions.is_empty() && valid_town_positions_water.is_empty() {
+        return Some(Warning::NoRoom { what: "towns" });
+    }
+
+    // Without inland spots every town goes on the coast
+    let town_num_water = if valid_town_positions.is_empty() {
+        cmp::min(valid_town_positions_water.len(), town_num)
+    } else {
+        cmp::min(valid_town_positions_water.len(), town_num / 2)
+    };
+    let town_num_dry = if valid_town_positions.is_empty() { 0 } else { town_num - town_num_water };
+
+    for _ in 0..town_num_water {
+
+        let rand_pos = rng.gen_range(0, valid_town_positions_water.len());
+        let coor = valid_town_positions_water[rand_pos];
+        let tile = tilemap.get(coor.0, coor.1);
+
+        let r_num = rng.gen_range(0, 1000);
+
+        let mut town_or_castle = "town";
+
+        if r_num < (sites.castle_chance_coast * 1000.0) as u32 {
+            town_or_castle = "castle";
+        }
+
+        let n_up = neighbor_coor(coor.0 as i32, coor.1 as i32, width, height, wrap, "up");
+        let n_down = neighbor_coor(coor.0 as i32, coor.1 as i32, width, height, wrap, "down");
+        let n_left = neighbor_coor(coor.0 as i32, coor.1 as i32, width, height, wrap, "left");
+        let n_right = neighbor_coor(coor.0 as i32, coor.1 as i32, width, height, wrap, "right");
+
+        let tile_up = tilemap.get(n_up.0, n_up.1);
+        let tile_down = tilemap.get(n_down.0, n_down.1);
+        let tile_left = tilemap.get(n_left.0, n_left.1);
+        let tile_right = tilemap.get(n_right.0, n_right.1);
+
+        if tile_up.dwelling || tile_down.dwelling || tile_left.dwelling || tile_right.dwelling {
+            continue;
+        }
+
+        tilemap.set_by_name(coor.0, coor.1, &[town_or_castle, "_", tile.cat.ground()].concat());
+    }
+
+    for _ in 0..town_num_dry {
+
+        let rand_pos = rng.gen_range(0, valid_town_positions.len());
+        let coor = valid_town_positions[rand_pos];
+        let tile = tilemap.get(coor.0, coor.1);
+
+        let r_num = rng.gen_range(0, 1000);
+
+        let mut town_or_castle = "town";
+
+        if r_num < (sites.castle_chance_inland * 1000.0) as u32 {
+            town_or_castle = "castle";
+        }
+
+        let n_up = neighbor_coor(coor.0 as i32, coor.1 as i32, width, height, wrap, "up");
+        let n_down = neighbor_coor(coor.0 as i32, coor.1 as i32, width, height, wrap, "down");
+        let n_left = neighbor_coor(coor.0 as i32, coor.1 as i32, width, height, wrap, "left");
+        let n_right = neighbor_coor(coor.0 as i32, coor.1 as i32, width, height, wrap, "right");
+
+        let tile_up = tilemap.get(n_up.0, n_up.1);
+        let tile_down = tilemap.get(n_down.0, n_down.1);
+        let tile_left = tilemap.get(n_left.0, n_left.1);
+        let tile_right = tilemap.get(n_right.0, n_right.1);
+
+        if tile_up.dwelling || tile_down.dwelling || tile_left.dwelling || tile_right.dwelling {
+            continue;
+        }
+
+        tilemap.set_by_name(coor.0, coor.1, &[town_or_castle, "_", tile.cat.ground()].concat());
+    }
+
+    None
+}
+
+// How well a tile feeds a town, from 0 for bare rock to 1 for grassland
+fn fertility(name: &str, cat: TileCategory) -> f32 {
+
+    match cat {
+        _ if name.starts_with("hill") || name.starts_with("mountain") => 0.0,
+        TileCategory::Grass if name.ends_with("forest") => 0.6,
+        TileCategory::Grass if name == "tundra" => 0.4,
+        TileCategory::Grass => 1.0,
+        TileCategory::Swamp => 0.3,
+        TileCategory::Sand => 0.2,
+        TileCategory::Snow | TileCategory::Ice => 0.1,
+        _ => 0.0,
+    }
+}
+
+// Towns / Castles on the best spots, scored for water nearby, flat land and 
+// fertile ground in the 5x5 tiles around them, and kept at least 
+// min_town_distance apart by Poisson-disk sampling. Every landmass large 
+// enough gets a town on its best spot, then towns spread out from there 
+// until each landmass has its share for its walkable land, then anywhere 
+// until there are enough. Warns about landmasses left without a town.
+pub fn place_towns_scored<R: Rng>(
+    tilemap: &mut Tilemap, 
+    river_map: &[Vec<bool>], 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    sites: &SiteConfig, 
+    rng: &mut R
+) -> Vec<Warning> {
+
+    let (landmass, landmass_tiles) = landmasses(tilemap, river_map, wrap);
+
+    let landmass_walkable: Vec<u32> = landmass_tiles.iter()
+        .map(|area| area.iter().filter(|&&(x, y)| tilemap.get(x, y).walkable).count() as u32)
+        .collect();
+
+    // Score, position, landmass and whether it is on the coast
+    let mut candidates = vec![];
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let tile = tilemap.get(x, y);
+
+            if !town_ground(tile) {
+                continue;
+            }
+
+            let next_to = neighbors(x, y, width, height, wrap);
+
+            if !next_to.iter().any(|&(nx, ny)| tilemap.get(nx, ny).walkable) {
+                continue;
+            }
+
+            let coast = next_to.iter().any(|&(nx, ny)| tilemap.get(nx, ny).cat == TileCategory::Water);
+
+            let mut water = false;
+            let mut flat = 0.0;
+            let mut fertile = 0.0;
+            let mut count = 0.0;
+
+            for dx in -2..=2 {
+                for dy in -2..=2 {
+
+                    if let Some((nx, ny)) = offset(x, y, (dx, dy), 1, width, height, wrap) {
+
+                        let near = tilemap.get(nx, ny);
+
+                        count += 1.0;
+                        water |= near.cat == TileCategory::Water;
+                        fertile += fertility(near.name, near.cat);
+
+                        if near.cat != TileCategory::Water && !near.name.starts_with("hill") && !near.name.starts_with("mountain") {
+                            flat += 1.0;
+                        }
+                    }
+                }
+            }
+
+            let score = if water { sites.water_weight } else { 0.0 }
+                + flat / count * sites.flat_weight
+                + fertile / count * sites.fertile_weight
+                + rng.gen::<f32>() * sites.score_jitter;
+
+            let land = landmass[x as usize][y as usize].unwrap();
+
+            candidates.push((score, (x, y), land, coast));
+        }
+    }
+
+    candidates.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+
+    let walkable_tiles: u32 = landmass_walkable.iter().sum();
+    let town_num = (walkable_tiles / sites.walkable_tiles_per_town) as usize;
+
+    if town_num > 0 && candidates.is_empty() {
+        return vec![Warning::NoRoom { what: "towns" }];
+    }
+
+    // Candidate at every position, to find the spots around a town
+    let mut at = vec![vec![None; height as usize]; width as usize];
+
+    for (i, &(_, (x, y), _, _)) in candidates.iter().enumerate() {
+        at[x as usize][y as usize] = Some(i);
+    }
+
+    let shares: Vec<usize> = landmass_walkable.iter().map(|&walkable| {
+        if walkable < sites.min_landmass_tiles {
+            0
+        } else {
+            cmp::max(1, (town_num as u64 * walkable as u64 / cmp::max(walkable_tiles, 1) as u64) as usize)
+        }
+    }).collect();
+
+    let mut towns: Vec<(u32, u32)> = vec![];
+    let mut taken = vec![false; candidates.len()];
+    let mut placed = vec![0; landmass_walkable.len()];
+    let mut warnings = vec![];
+
+    let far_enough = |towns: &[(u32, u32)], coor: (u32, u32)| towns.iter().all(|&town| {
+        let (dx, dy) = axis_dist(town, coor, width, height, wrap);
+        ((dx * dx + dy * dy) as f32).sqrt() >= sites.min_town_distance
+    });
+
+    // Smallest landmasses first, so one across a narrow strait from a 
+    // larger one isn't crowded out of its only town
+    let mut order: Vec<usize> = (0..landmass_walkable.len()).filter(|&land| shares[land] > 0).collect();
+    order.sort_by_key(|&land| landmass_walkable[land]);
+
+    for land in order {
+        match candidates.iter().position(|c| c.2 == land && far_enough(&towns, c.1)) {
+            Some(i) => {
+                towns.push(candidates[i].1);
+                taken[i] = true;
+                placed[land] += 1;
+            }
+            None => warnings.push(Warning::Townless { tiles: landmass_walkable[land] }),
+        }
+    }
+
+    // Bridson's algorithm over the candidates: a random active town takes 
+    // the best spot between one and two times min_town_distance away that 
+    // is far enough from every town, and stops being active when there is 
+    // none. When no town is active the best spot left anywhere starts a 
+    // new one, for land out of reach of the others.
+    let reach = (2.0 * sites.min_town_distance).ceil() as i32;
+
+    for &shared in [true, false].iter() {
+
+        // Taken spots are never far enough from the town on them
+        let open = |i: usize, placed: &[usize], towns: &[(u32, u32)]| {
+            let land = candidates[i].2;
+            (!shared || placed[land] < shares[land]) && far_enough(towns, candidates[i].1)
+        };
+
+        let mut active = towns.clone();
+
+        while shared || towns.len() < town_num {
+
+            let (picked, best) = if active.is_empty() {
+                (None, (0..candidates.len()).find(|&i| open(i, &placed, &towns)))
+            } else {
+                let picked = rng.gen_range(0, active.len());
+                let (x, y) = active[picked];
+
+                let best = (-reach..=reach)
+                    .flat_map(|dx| (-reach..=reach).map(move |dy| (dx, dy)))
+                    .filter(|&(dx, dy)| {
+                        let dist = ((dx * dx + dy * dy) as f32).sqrt();
+                        dist >= sites.min_town_distance && dist <= 2.0 * sites.min_town_distance
+                    })
+                    .filter_map(|d| offset(x, y, d, 1, width, height, wrap))
+                    .filter_map(|(nx, ny)| at[nx as usize][ny as usize])
+                    .filter(|&i| open(i, &placed, &towns))
+                    .min();
+
+                (Some(picked), best)
+            };
+
+            match (best, picked) {
+                (Some(i), _) => {
+                    towns.push(candidates[i].1);
+                    active.push(candidates[i].1);
+                    taken[i] = true;
+                    placed[candidates[i].2] += 1;
+                }
+                (None, Some(picked)) => {
+                    active.swap_remove(picked);
+                }
+                (None, None) => break,
+            }
+        }
+    }
+
+    for (i, &(_, coor, _, coast)) in candidates.iter().enumerate() {
+
+        if !taken[i] {
+            continue;
+        }
+
+        let chance = if coast { sites.castle_chance_coast } else { sites.castle_chance_inland };
+        let town_or_castle = if rng.gen::<f32>() < chance { "castle" } else { "town" };
+        let tile = tilemap.get(coor.0, coor.1);
+
+        tilemap.set_by_name(coor.0, coor.1, &[town_or_castle, "_", tile.cat.ground()].concat());
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::tile::Tilelist;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use std::sync::Arc;
+
+    // Grass with one straight river down the middle
+    fn river_map() -> (Tilemap, Vec<Vec<bool>>) {
+
+        let mut tilemap = Tilemap::new(9, 9, "grass", &Arc::new(Tilelist::old_school()));
+        let mut river_map = vec![vec![false; 9]; 9];
+
+        for y in 0..9 {
+            tilemap.set_by_name(4, y, "water_0000");
+            river_map[4][y as usize] = true;
+        }
+
+        (tilemap, river_map)
+    }
+
+    fn bridges(tilemap: &Tilemap) -> usize {
+
+        (0..9).flat_map(|x| (0..9).map(move |y| (x, y))).filter(|&(x, y)| tilemap.get(x, y).bridge).count()
+    }
+
+    #[test]
+    fn too_few_bridge_spots_warns() {
+
+        let (mut tilemap, river_map) = river_map();
+        let sites = SiteConfig { bridges_per_river: 3, bridge_spacing: 3, ..SiteConfig::default() };
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+
+        // Six bridges asked for, only three fit three tiles apart on nine
+        let warning = place_bridges(&mut tilemap, &river_map, 2, 9, 9, false, &sites, &mut rng);
+
+        match warning {
+            Some(Warning::FewerBridges { requested, placed, candidates }) => {
+                assert_eq!(requested, 6);
+                assert!(placed < 6 && placed <= candidates);
+                assert_eq!(placed, bridges(&tilemap));
+            }
+            _ => panic!("expected a FewerBridges warning, got {:?}", warning),
+        }
+    }
+
+    #[test]
+    fn scored_towns_skip_hills_and_mountains() {
+
+        let mut tilemap = Tilemap::new(40, 40, "grass", &Arc::new(Tilelist::old_school()));
+
+        // A mountain range down the middle with foothills on both sides
+        for y in 0..40 {
+            for x in 16..24 {
+                let name = if x == 16 || x == 23 { "hill_grass" } else { "mountain_grass" };
+                tilemap.set_by_name(x, y, name);
+            }
+        }
+
+        let before = tilemap.clone();
+        let sites = SiteConfig { 
+            walkable_tiles_per_town: 50, 
+            min_town_distance: 4.0, 
+            flat_weight: 0.0, 
+            score_jitter: 1.0, 
+            ..SiteConfig::default() 
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+
+        let river_map = vec![vec![false; 40]; 40];
+
+        place_towns_scored(&mut tilemap, &river_map, 40, 40, false, &sites, &mut rng);
+
+        let mut towns = 0;
+
+        for x in 0..40 {
+            for y in 0..40 {
+                if tilemap.get(x, y).dwelling {
+                    towns += 1;
+                    let was = before.get(x, y).name;
+                    assert!(!was.starts_with("hill") && !was.starts_with("mountain"), "town on {} at {:?}", was, (x, y));
+                }
+            }
+        }
+
+        assert!(towns > 0);
+    }
+
+    #[test]
+    fn random_towns_skip_hills_mountains_and_caves() {
+
+        let mut tilemap = Tilemap::new(40, 40, "grass", &Arc::new(Tilelist::old_school()));
+
+        // Mostly rough ground with a few caves, so random picks would hit it
+        for x in 0..40 {
+            for y in 0..40 {
+                let name = match (x + y) % 4 {
+                    0 => "hill_grass",
+                    1 => "mountain_grass",
+                    2 if x % 5 == 0 => "cave_grass",
+                    _ => "grass",
+                };
+                tilemap.set_by_name(x, y, name);
+            }
+        }
+
+        let before = tilemap.clone();
+        let sites = SiteConfig { walkable_tiles_per_town: 20, ..SiteConfig::default() };
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+
+        place_towns(&mut tilemap, 40, 40, false, &sites, &mut rng);
+
+        let mut towns = 0;
+
+        for x in 0..40 {
+            for y in 0..40 {
+                if tilemap.get(x, y).dwelling {
+                    towns += 1;
+                    let was = before.get(x, y);
+                    assert!(was.walkable, "town on {} at {:?}", was.name, (x, y));
+                    assert!(!was.name.starts_with("hill") && !was.name.starts_with("cave"), "town on {} at {:?}", was.name, (x, y));
+                }
+            }
+        }
+
+        assert!(towns > 0);
+    }
+
+    // The river doesn't cut the island in two, so it needs one town, not one 
+    // on each bank
+    #[test]
+    fn river_doesnt_split_landmass() {
+
+        let (mut tilemap, river_map) = river_map();
+        let sites = SiteConfig { min_town_distance: 20.0, min_landmass_tiles: 20, ..SiteConfig::default() };
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+
+        let warnings = place_towns_scored(&mut tilemap, &river_map, 9, 9, false, &sites, &mut rng);
+
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!((0..9).flat_map(|x| (0..9).map(move |y| (x, y))).filter(|&(x, y)| tilemap.get(x, y).dwelling).count(), 1);
+    }
+
+    #[test]
+    fn enough_bridge_spots_doesnt_warn() {
+
+        let (mut tilemap, river_map) = river_map();
+        let sites = SiteConfig { bridges_per_river: 2, bridge_spacing: 3, ..SiteConfig::default() };
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+
+        assert_eq!(place_bridges(&mut tilemap, &river_map, 1, 9, 9, false, &sites, &mut rng), None);
+        assert_eq!(bridges(&tilemap), 2);
+    }
+}
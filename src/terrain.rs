@@ -0,0 +1,351 @@
+use terr::heightmap::Heightmap;
+
+use rand::prelude::*; // Random
+use std::path::Path;
+
+use crate::config::{TerrainConfig, BiomeConfig, OceanConfig};
+use crate::error::Error;
+use crate::grid::neighbor_coor;
+use crate::heightmap::{apply_simplex, blended_heightmap};
+use crate::render::test_png_hm;
+use crate::tile::{Tilemap, TileCategory};
+
+// Determine water, grass, hill and mountain based on heightmap
+pub fn classify_terrain(
+    tilemap: &mut Tilemap, 
+    heightmap: &Heightmap<f32>, 
+    width: u32, 
+    height: u32, 
+    terrain: &TerrainConfig
+) {
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let h_val = heightmap.get(x, y);
+            let t_name = if h_val < terrain.cutoff_water {
+                "water_0000"
+            } else if h_val < terrain.cutoff_terrain {
+               "grass"
+            } else if h_val < terrain.cutoff_hill {
+                "hill_grass"
+            } else {
+                "mountain_grass"
+            };
+
+            tilemap.set_by_name(x, y, t_name);
+        }
+    }
+}
+
+// Determine forest & desert with simplex noise
+// Low parts are forest, high are desert
+// Only apply forest to grass
+// Desert can apply to grass, hills and mountain
+pub fn paint_forest_desert<R: Rng>(
+    tilemap: &mut Tilemap, 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    biomes: &BiomeConfig, 
+    rng: &mut R, 
+    debug_dir: Option<&Path>
+) -> Result<(), Error> {
+
+    let mut fd_hm1 = Heightmap::new_flat((width, height), (0.0, 0.0));
+    let mut fd_hm2 = Heightmap::new_flat((width, height), (0.0, 0.0));
+
+    apply_simplex(&mut fd_hm1, width, height, wrap, biomes.fine_noise_scale, rng.gen());
+    apply_simplex(&mut fd_hm2, width, height, wrap, biomes.coarse_noise_scale, rng.gen());
+
+    if let Some(dir) = debug_dir {
+        test_png_hm(&fd_hm1, width, height, dir, "test6")?;
+    }
+    if let Some(dir) = debug_dir {
+        test_png_hm(&fd_hm2, width, height, dir, "test7")?;
+    }
+
+    // combine simplex noise with a finer simplex noise, for more details
+    let forest_desert_hm = blended_heightmap(fd_hm1, fd_hm2, width, height);
+
+    if let Some(dir) = debug_dir {
+        test_png_hm(&forest_desert_hm, width, height, dir, "test8")?;
+    }
+
+    // Determine forest and desert tiles based on combined noise map
+    for x in 0..width {
+        for y in 0..height {
+
+            let tile = tilemap.get(x, y);
+            let fd_val = forest_desert_hm.get(x, y);
+            let r_num = rng.gen_range(1, 1001);
+
+            if fd_val > biomes.forest_above {
+                if tile.name == "grass" {
+                    tilemap.set_by_name(x, y, "forest");
+                }
+            } else if fd_val > biomes.thicker_grass_above {
+                if tile.name == "grass" {
+                    if r_num > 250 {
+                        tilemap.set_by_name(x, y, "thicker_grass");
+                    } else if r_num > 63 {
+                        tilemap.set_by_name(x, y, "thick_grass");
+                    } else if r_num > 16 {
+                        tilemap.set_by_name(x, y, "flowers");
+                    }
+                }
+            } else if fd_val > biomes.thick_grass_above {
+                if tile.name == "grass" {
+                    if r_num > 500 {
+                        tilemap.set_by_name(x, y, "thick_grass");
+                    } else if r_num > 250 {
+                        tilemap.set_by_name(x, y, "flowers");
+                    }
+                }
+            } else if fd_val > biomes.flowers_above {
+                if tile.name == "grass" && r_num > 950 {
+                    tilemap.set_by_name(x, y, "flowers");
+                }
+            } else if fd_val < biomes.desert_below {
+                if tile.name == "grass" {
+                    tilemap.set_by_name(x, y, "sand_0000");
+                } else if tile.name == "hill_grass" {
+                    tilemap.set_by_name(x, y, "hill_sand");
+                } else if tile.name == "mountain_grass" {
+                    tilemap.set_by_name(x, y, "mountain_sand");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Add wetlands / swamp
+pub fn paint_swamps<R: Rng>(
+    tilemap: &mut Tilemap, 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    biomes: &BiomeConfig, 
+    rng: &mut R
+) {
+
+    let mut swamp_hm = Heightmap::new_flat((width, height), (0.0, 0.0));
+
+    apply_simplex(&mut swamp_hm, width, height, wrap, biomes.swamp_noise_scale, rng.gen());
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let tile = tilemap.get(x, y);
+            let s_val = swamp_hm.get(x, y);
+
+            if s_val > biomes.swamp_above && tile.cat == TileCategory::Grass {
+                tilemap.set_by_name(x, y, "swamp");
+            }
+        }
+    }
+}
+
+// Generate coastline dunes
+pub fn paint_dunes<R: Rng>(
+    tilemap: &mut Tilemap, 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    biomes: &BiomeConfig, 
+    rng: &mut R
+) {
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let tile = tilemap.get(x, y);
+
+            if tile.cat == TileCategory::Grass {
+
+                let mut next_to_water = false;
+
+                let coor_up = neighbor_coor(x as i32, y as i32, width, height, wrap, "up");
+                let n_up = tilemap.get(coor_up.0, coor_up.1);
+
+                if n_up.cat == TileCategory::Water {
+                    next_to_water = true;
+                } else {
+
+                    let coor_down = neighbor_coor(x as i32, y as i32, width, height, wrap, "down");
+                    let n_down = tilemap.get(coor_down.0, coor_down.1);
+
+                    if n_down.cat == TileCategory::Water {
+                        next_to_water = true;
+                    } else {
+
+                        let coor_left = neighbor_coor(x as i32, y as i32, width, height, wrap, "left");
+                        let n_left = tilemap.get(coor_left.0, coor_left.1);
+
+                        if n_left.cat == TileCategory::Water {
+                            next_to_water = true;
+                        } else {
+
+                            let coor_right = neighbor_coor(x as i32, y as i32, width, height, wrap, "right");
+                            let n_right = tilemap.get(coor_right.0, coor_right.1);
+
+                            if n_right.cat == TileCategory::Water {
+                                next_to_water = true;
+                            }
+                        }
+                    }
+                }
+
+                let r_num = rng.gen_range(1, 1001);
+
+                if next_to_water && r_num < (biomes.dune_chance * 1000.0) as u32 {
+                    tilemap.set_by_name(x, y, "sand_0000");
+                }
+            }
+        }
+    }
+}
+
+// Do transition tiles for water, sand & snow. Frozen sea counts as water, 
+// so coasts along it get no edge.
+pub fn transition_tiles(tilemap: &mut Tilemap, width: u32, height: u32, wrap: bool) {
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let tile = tilemap.get(x, y);
+
+            let n_up = neighbor_coor(x as i32, y as i32, width, height, wrap, "up");
+            let n_down = neighbor_coor(x as i32, y as i32, width, height, wrap, "down");
+            let n_left = neighbor_coor(x as i32, y as i32, width, height, wrap, "left");
+            let n_right = neighbor_coor(x as i32, y as i32, width, height, wrap, "right");
+
+            let tile_up = tilemap.get(n_up.0, n_up.1);
+            let tile_down = tilemap.get(n_down.0, n_down.1);
+            let tile_left = tilemap.get(n_left.0, n_left.1);
+            let tile_right = tilemap.get(n_right.0, n_right.1);
+
+            if tile.name == "sand_0000" {
+
+                let mut t_str = String::from("sand_");
+
+                // Order is important: up, right, down, left
+
+                if tile_up.cat == TileCategory::Sand || tile_up.is_water() {
+                    t_str.push('0');
+                } else {
+                    t_str.push('1');
+                }
+
+                if tile_right.cat == TileCategory::Sand || tile_right.is_water() {
+                    t_str.push('0');
+                } else {
+                    t_str.push('1');
+                }
+
+                if tile_down.cat == TileCategory::Sand || tile_down.is_water() {
+                    t_str.push('0');
+                } else {
+                    t_str.push('1');
+                }
+
+                if tile_left.cat == TileCategory::Sand || tile_left.is_water() {
+                    t_str.push('0');
+                } else {
+                    t_str.push('1');
+                }
+                tilemap.set_by_name(x, y, &t_str);
+
+            } else if tile.name == "snow_0000" {
+
+                let mut t_str = String::from("snow_");
+
+                // Order is important: up, right, down, left
+
+                for n_tile in [tile_up, tile_right, tile_down, tile_left].iter() {
+                    if n_tile.cat == TileCategory::Snow || n_tile.cat == TileCategory::Ice || n_tile.is_water() {
+                        t_str.push('0');
+                    } else {
+                        t_str.push('1');
+                    }
+                }
+                tilemap.set_by_name(x, y, &t_str);
+
+            } else if tile.name == "water_0000" {
+
+                let mut t_str = String::from("water_");
+
+                // Order is important: up, right, down, left
+
+                if tile_up.is_water() {
+                    t_str.push('0');
+                } else {
+                    t_str.push('1');
+                }
+
+                if tile_right.is_water() {
+                    t_str.push('0');
+                } else {
+                    t_str.push('1');
+                }
+
+                if tile_down.is_water() {
+                    t_str.push('0');
+                } else {
+                    t_str.push('1');
+                }
+
+                if tile_left.is_water() {
+                    t_str.push('0');
+                } else {
+                    t_str.push('1');
+                }
+                tilemap.set_by_name(x, y, &t_str);
+            }
+        }
+    }
+}
+
+// Split the sea into shallows, open sea and deep ocean by the heightmap. 
+// Runs last so shore edges are already picked: coast tiles keep them, deep 
+// water only starts away from land and reefs only grow in open shallows.
+pub fn paint_depths<R: Rng>(
+    tilemap: &mut Tilemap, 
+    heightmap: &Heightmap<f32>, 
+    width: u32, 
+    height: u32, 
+    wrap: bool, 
+    terrain: &TerrainConfig, 
+    ocean: &OceanConfig, 
+    rng: &mut R
+) {
+
+    let mut reef_hm = Heightmap::new_flat((width, height), (0.0, 0.0));
+
+    apply_simplex(&mut reef_hm, width, height, wrap, ocean.reef_noise_scale, rng.gen());
+
+    for x in 0..width {
+        for y in 0..height {
+
+            let tile = tilemap.get(x, y);
+            let h_val = heightmap.get(x, y);
+
+            // Lakes and rivers are above the water cutoff
+            if h_val >= terrain.cutoff_water || !tile.name.starts_with("water_") {
+                continue;
+            }
+
+            if h_val >= ocean.shallow_above {
+                if tile.name == "water_0000" && reef_hm.get(x, y) > ocean.reef_above {
+                    tilemap.set_by_name(x, y, "reef");
+                } else {
+                    tilemap.set_by_name(x, y, &["shallows_", &tile.name[6..]].concat());
+                }
+            } else if h_val < ocean.deep_below && tile.name == "water_0000" {
+                tilemap.set_by_name(x, y, "deep_water");
+            }
+        }
+    }
+}
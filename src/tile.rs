@@ -0,0 +1,370 @@
+use std::collections::HashMap; // For name to id lookup
+use std::fmt;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TileCategory {
+    Grass,
+    Sand,
+    Swamp,
+    Water,
+    Snow,
+    Ice,
+    FrozenWater,
+    Dungeon,
+    Interior,
+}
+
+impl TileCategory {
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TileCategory::Grass => "grass",
+            TileCategory::Sand => "sand",
+            TileCategory::Swamp => "swamp",
+            TileCategory::Water => "water",
+            TileCategory::Snow => "snow",
+            TileCategory::Ice => "ice",
+            TileCategory::FrozenWater => "frozen_water",
+            TileCategory::Dungeon => "dungeon",
+            TileCategory::Interior => "interior",
+        }
+    }
+
+    // Ground that hills, mountains, caves, towns and castles on this 
+    // category are drawn on, the last part of their tile names
+    pub fn ground(&self) -> &'static str {
+        match self {
+            TileCategory::Sand => "sand",
+            TileCategory::Snow | TileCategory::Ice | TileCategory::FrozenWater => "snow",
+            _ => "grass",
+        }
+    }
+}
+
+impl fmt::Display for TileCategory {
+
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Tile {
+    pub name: &'static str,
+    pub cat: TileCategory,
+    pub walkable: bool,
+    pub bridge: bool,
+    pub dwelling: bool,
+    pub id: u16,
+}
+
+impl Tile {
+
+    pub fn new(name: &'static str, cat: TileCategory, walkable: bool, bridge: bool, dwelling: bool, id: u16) -> Tile {
+        Tile { 
+            name, 
+            cat, 
+            walkable, 
+            bridge,
+            dwelling,
+            id 
+        }
+    }
+
+    // Open or frozen water. Neither can be walked on, so both keep 
+    // landmasses apart.
+    pub fn is_water(&self) -> bool {
+        self.cat == TileCategory::Water || self.cat == TileCategory::FrozenWater
+    }
+}
+
+// Grid of tile ids, indexed as [y * width + x]. Tile details are looked up 
+// in the shared Tilelist when needed.
+#[derive(Clone)]
+pub struct Tilemap {
+    width: u32,
+    height: u32,
+    ids: Vec<u16>,
+    tilelist: Arc<Tilelist>,
+}
+
+impl Tilemap {
+
+    pub fn new(width: u32, height: u32, tilename: &str, tilelist: &Arc<Tilelist>) -> Self {
+
+        let id = tilelist.id_at_name(tilename).unwrap();
+
+        Tilemap { 
+            width, 
+            height, 
+            ids: vec![id; (width * height) as usize], 
+            tilelist: Arc::clone(tilelist) 
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+
+        self.height
+    }
+
+    pub fn tilelist(&self) -> &Arc<Tilelist> {
+
+        &self.tilelist
+    }
+
+    pub fn id(&self, x: u32, y: u32) -> u16 {
+
+        // A wrong x past the edge would land on the next row instead of panicking
+        debug_assert!(x < self.width && y < self.height);
+        self.ids[(y * self.width + x) as usize]
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Tile {
+
+        self.tilelist.tile_at_id(self.id(x, y)).unwrap()
+    }
+
+    pub fn set_id(&mut self, x: u32, y: u32, id: u16) {
+
+        debug_assert!(x < self.width && y < self.height);
+        self.ids[(y * self.width + x) as usize] = id;
+    }
+
+    pub fn set_by_name(&mut self, x: u32, y: u32, name: &str) {
+
+        self.set_id(x, y, self.tilelist.id_at_name(name).unwrap());
+    }
+}
+
+#[derive(Clone)]
+pub struct Tilelist {
+    tiles: Vec<Tile>,
+    by_name: HashMap<&'static str, u16>,
+    by_id: Vec<Option<usize>>, // Position in tiles for each id
+}
+
+impl Tilelist {
+
+    pub fn new(tiles: Vec<Tile>) -> Tilelist {
+
+        let by_name = tiles.iter().map(|t| (t.name, t.id)).collect();
+
+        let max_id = tiles.iter().map(|t| t.id as usize).max().unwrap_or(0);
+        let mut by_id = vec![None; max_id + 1];
+
+        for (i, t) in tiles.iter().enumerate() {
+            by_id[t.id as usize] = Some(i);
+        }
+
+        Tilelist { tiles, by_name, by_id }
+    }
+
+    // Tile information matching the layout of "old_school_tiles.png"
+    pub fn old_school() -> Tilelist {
+
+        use TileCategory::*;
+
+        Tilelist::new(vec![
+            Tile::new("grass",             Grass, true,  false, false, 0),
+            Tile::new("flowers",           Grass, true,  false, false, 1),
+            Tile::new("thick_grass",       Grass, true,  false, false, 2),
+            Tile::new("thicker_grass",     Grass, true,  false, false, 3),
+            Tile::new("forest",            Grass, true,  false, false, 4),
+            Tile::new("swamp",             Swamp, true,  false, false, 5),
+            Tile::new("castle_grass",      Grass, true,  false, true,  6),
+            Tile::new("town_grass",        Grass, true,  false, true,  7),
+            Tile::new("castle_sand",       Sand,  true,  false, true,  8),
+            Tile::new("town_sand",         Sand,  true,  false, true,  9),
+            Tile::new("bridge_up_down",    Water, true,  true,  false, 10),
+            Tile::new("bridge_left_right", Water, true,  true,  false, 11),
+            Tile::new("water_0000",        Water, false, false, false, 12),
+            Tile::new("sand_0000",         Sand,  true,  false, false, 13),
+            Tile::new("cave_grass",        Grass, true,  false, false, 14),
+            Tile::new("hill_grass",        Grass, true,  false, false, 15),
+            Tile::new("mountain_grass",    Grass, false, false, false, 16),
+            Tile::new("hill_sand",         Sand,  true,  false, false, 17),
+            Tile::new("mountain_sand",     Sand,  false, false, false, 18),
+            Tile::new("cave_sand",         Sand,  true,  false, false, 19),
+            Tile::new("water_1111",        Water, false, false, false, 20),
+            Tile::new("water_1001",        Water, false, false, false, 21),
+            Tile::new("water_1100",        Water, false, false, false, 22),
+            Tile::new("water_0011",        Water, false, false, false, 23),
+            Tile::new("water_0110",        Water, false, false, false, 24),
+            Tile::new("water_1010",        Water, false, false, false, 25),
+            Tile::new("water_1101",        Water, false, false, false, 26),
+            Tile::new("water_1110",        Water, false, false, false, 27),
+            Tile::new("water_1011",        Water, false, false, false, 28),
+            Tile::new("water_0111",        Water, false, false, false, 29),
+            Tile::new("water_0101",        Water, false, false, false, 30),
+            Tile::new("water_1000",        Water, false, false, false, 31),
+            Tile::new("water_0100",        Water, false, false, false, 32),
+            Tile::new("water_0010",        Water, false, false, false, 33),
+            Tile::new("water_0001",        Water, false, false, false, 34),
+            Tile::new("sand_1111",         Sand,  true,  false, false, 35),
+            Tile::new("sand_1001",         Sand,  true,  false, false, 36),
+            Tile::new("sand_1100",         Sand,  true,  false, false, 37),
+            Tile::new("sand_0011",         Sand,  true,  false, false, 38),
+            Tile::new("sand_0110",         Sand,  true,  false, false, 39),
+            Tile::new("sand_1010",         Sand,  true,  false, false, 40),
+            Tile::new("sand_1101",         Sand,  true,  false, false, 41),
+            Tile::new("sand_1110",         Sand,  true,  false, false, 42),
+            Tile::new("sand_1011",         Sand,  true,  false, false, 43),
+            Tile::new("sand_0111",         Sand,  true,  false, false, 44),
+            Tile::new("sand_0101",         Sand,  true,  false, false, 45),
+            Tile::new("sand_1000",         Sand,  true,  false, false, 46),
+            Tile::new("sand_0100",         Sand,  true,  false, false, 47),
+            Tile::new("sand_0010",         Sand,  true,  false, false, 48),
+            Tile::new("sand_0001",         Sand,  true,  false, false, 49),
+            // Roads, the digits say if the road continues up, right, down and left
+            Tile::new("road_0000",         Grass, true,  false, false, 50),
+            Tile::new("road_0001",         Grass, true,  false, false, 51),
+            Tile::new("road_0010",         Grass, true,  false, false, 52),
+            Tile::new("road_0011",         Grass, true,  false, false, 53),
+            Tile::new("road_0100",         Grass, true,  false, false, 54),
+            Tile::new("road_0101",         Grass, true,  false, false, 55),
+            Tile::new("road_0110",         Grass, true,  false, false, 56),
+            Tile::new("road_0111",         Grass, true,  false, false, 57),
+            Tile::new("road_1000",         Grass, true,  false, false, 58),
+            Tile::new("road_1001",         Grass, true,  false, false, 59),
+            Tile::new("road_1010",         Grass, true,  false, false, 60),
+            Tile::new("road_1011",         Grass, true,  false, false, 61),
+            Tile::new("road_1100",         Grass, true,  false, false, 62),
+            Tile::new("road_1101",         Grass, true,  false, false, 63),
+            Tile::new("road_1110",         Grass, true,  false, false, 64),
+            Tile::new("road_1111",         Grass, true,  false, false, 65),
+            Tile::new("road_sand_0000",    Sand,  true,  false, false, 66),
+            Tile::new("road_sand_0001",    Sand,  true,  false, false, 67),
+            Tile::new("road_sand_0010",    Sand,  true,  false, false, 68),
+            Tile::new("road_sand_0011",    Sand,  true,  false, false, 69),
+            Tile::new("road_sand_0100",    Sand,  true,  false, false, 70),
+            Tile::new("road_sand_0101",    Sand,  true,  false, false, 71),
+            Tile::new("road_sand_0110",    Sand,  true,  false, false, 72),
+            Tile::new("road_sand_0111",    Sand,  true,  false, false, 73),
+            Tile::new("road_sand_1000",    Sand,  true,  false, false, 74),
+            Tile::new("road_sand_1001",    Sand,  true,  false, false, 75),
+            Tile::new("road_sand_1010",    Sand,  true,  false, false, 76),
+            Tile::new("road_sand_1011",    Sand,  true,  false, false, 77),
+            Tile::new("road_sand_1100",    Sand,  true,  false, false, 78),
+            Tile::new("road_sand_1101",    Sand,  true,  false, false, 79),
+            Tile::new("road_sand_1110",    Sand,  true,  false, false, 80),
+            Tile::new("road_sand_1111",    Sand,  true,  false, false, 81),
+            // Snow, the digits say which sides border ground other than snow, ice or water
+            Tile::new("snow_0000",         Snow,  true,  false, false, 82),
+            Tile::new("snow_0001",         Snow,  true,  false, false, 83),
+            Tile::new("snow_0010",         Snow,  true,  false, false, 84),
+            Tile::new("snow_0011",         Snow,  true,  false, false, 85),
+            Tile::new("snow_0100",         Snow,  true,  false, false, 86),
+            Tile::new("snow_0101",         Snow,  true,  false, false, 87),
+            Tile::new("snow_0110",         Snow,  true,  false, false, 88),
+            Tile::new("snow_0111",         Snow,  true,  false, false, 89),
+            Tile::new("snow_1000",         Snow,  true,  false, false, 90),
+            Tile::new("snow_1001",         Snow,  true,  false, false, 91),
+            Tile::new("snow_1010",         Snow,  true,  false, false, 92),
+            Tile::new("snow_1011",         Snow,  true,  false, false, 93),
+            Tile::new("snow_1100",         Snow,  true,  false, false, 94),
+            Tile::new("snow_1101",         Snow,  true,  false, false, 95),
+            Tile::new("snow_1110",         Snow,  true,  false, false, 96),
+            Tile::new("snow_1111",         Snow,  true,  false, false, 97),
+            Tile::new("snow_forest",       Snow,  true,  false, false, 98),
+            Tile::new("hill_snow",         Snow,  true,  false, false, 99),
+            Tile::new("mountain_snow",     Snow,  false, false, false, 100),
+            Tile::new("cave_snow",         Snow,  true,  false, false, 101),
+            Tile::new("town_snow",         Snow,  true,  false, true,  102),
+            Tile::new("castle_snow",       Snow,  true,  false, true,  103),
+            Tile::new("tundra",            Grass, true,  false, false, 104),
+            Tile::new("ice",               Ice,   true,  false, false, 105),
+            Tile::new("frozen_water",      FrozenWater, false, false, false, 106),
+            // Roads over snow, digits as for the other roads
+            Tile::new("road_snow_0000",    Snow,  true,  false, false, 107),
+            Tile::new("road_snow_0001",    Snow,  true,  false, false, 108),
+            Tile::new("road_snow_0010",    Snow,  true,  false, false, 109),
+            Tile::new("road_snow_0011",    Snow,  true,  false, false, 110),
+            Tile::new("road_snow_0100",    Snow,  true,  false, false, 111),
+            Tile::new("road_snow_0101",    Snow,  true,  false, false, 112),
+            Tile::new("road_snow_0110",    Snow,  true,  false, false, 113),
+            Tile::new("road_snow_0111",    Snow,  true,  false, false, 114),
+            Tile::new("road_snow_1000",    Snow,  true,  false, false, 115),
+            Tile::new("road_snow_1001",    Snow,  true,  false, false, 116),
+            Tile::new("road_snow_1010",    Snow,  true,  false, false, 117),
+            Tile::new("road_snow_1011",    Snow,  true,  false, false, 118),
+            Tile::new("road_snow_1100",    Snow,  true,  false, false, 119),
+            Tile::new("road_snow_1101",    Snow,  true,  false, false, 120),
+            Tile::new("road_snow_1110",    Snow,  true,  false, false, 121),
+            Tile::new("road_snow_1111",    Snow,  true,  false, false, 122),
+            // Shallow water along coasts, digits as for water
+            Tile::new("shallows_0000",     Water, false, false, false, 123),
+            Tile::new("shallows_0001",     Water, false, false, false, 124),
+            Tile::new("shallows_0010",     Water, false, false, false, 125),
+            Tile::new("shallows_0011",     Water, false, false, false, 126),
+            Tile::new("shallows_0100",     Water, false, false, false, 127),
+            Tile::new("shallows_0101",     Water, false, false, false, 128),
+            Tile::new("shallows_0110",     Water, false, false, false, 129),
+            Tile::new("shallows_0111",     Water, false, false, false, 130),
+            Tile::new("shallows_1000",     Water, false, false, false, 131),
+            Tile::new("shallows_1001",     Water, false, false, false, 132),
+            Tile::new("shallows_1010",     Water, false, false, false, 133),
+            Tile::new("shallows_1011",     Water, false, false, false, 134),
+            Tile::new("shallows_1100",     Water, false, false, false, 135),
+            Tile::new("shallows_1101",     Water, false, false, false, 136),
+            Tile::new("shallows_1110",     Water, false, false, false, 137),
+            Tile::new("shallows_1111",     Water, false, false, false, 138),
+            Tile::new("deep_water",        Water, false, false, false, 139),
+            Tile::new("reef",              Water, false, false, false, 140),
+            // Dungeon floors behind caves, stairs_up on the first floor leads back outside
+            Tile::new("dungeon_floor",     Dungeon, true,  false, false, 141),
+            Tile::new("dungeon_wall",      Dungeon, false, false, false, 142),
+            Tile::new("stairs_up",         Dungeon, true,  false, false, 143),
+            Tile::new("stairs_down",       Dungeon, true,  false, false, 144),
+            Tile::new("chest",             Dungeon, true,  false, false, 145),
+            Tile::new("boss",              Dungeon, true,  false, false, 146),
+            // Inside towns and castles, on ground tiles from the overworld
+            Tile::new("town_wall",         Interior, false, false, false, 147),
+            Tile::new("town_gate",         Interior, true,  false, false, 148),
+            Tile::new("roof_grass",        Interior, false, false, false, 149),
+            Tile::new("roof_sand",         Interior, false, false, false, 150),
+            Tile::new("roof_snow",         Interior, false, false, false, 151),
+            Tile::new("door",              Interior, true,  false, false, 152),
+            Tile::new("door_inn",          Interior, true,  false, false, 153),
+            Tile::new("door_shop",         Interior, true,  false, false, 154),
+            Tile::new("door_church",       Interior, true,  false, false, 155),
+            Tile::new("well",              Interior, false, false, false, 156),
+            Tile::new("town_path",         Interior, true,  false, false, 157),
+            Tile::new("castle_floor",      Interior, true,  false, false, 158),
+            Tile::new("carpet",            Interior, true,  false, false, 159),
+            Tile::new("throne",            Interior, false, false, false, 160),
+            Tile::new("tower",             Interior, false, false, false, 161),
+        ])
+    }
+
+    pub fn tiles(&self) -> &[Tile] {
+
+        &self.tiles
+    }
+
+    pub fn id_at_name(&self, name: &str) -> Option<u16> {
+
+        self.by_name.get(name).copied()
+    }
+
+    pub fn tile_at_name(&self, name: &str) -> Result<Tile, &str> {
+
+        match self.id_at_name(name) {
+            Some(id) => self.tile_at_id(id),
+            None => Err("Name not found in Tilelist.")
+        }
+    }
+
+    pub fn tile_at_id(&self, id: u16) -> Result<Tile, &str> {
+
+        match self.by_id.get(id as usize) {
+            Some(Some(i)) => Ok(self.tiles[*i]),
+            _ => Err("Id not found in Tilelist.")
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use std::sync::Arc;
+use terr::heightmap::Heightmap;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng; // Seeded random, same sequence on every platform
+
+use crate::climate::{Climate, compute_climate, paint_climate, paint_polar};
+use crate::config::{Config, RiverMode, TownPlacement};
+use crate::connectivity::{Connectivity, repair_connectivity};
+use crate::dungeon::{Dungeon, build_dungeons};
+use crate::erosion::erode;
+use crate::error::{Error, Warning};
+use crate::heightmap::build_heightmap;
+use crate::interior::{Interior, build_interiors};
+use crate::kingdoms::{Kingdoms, build_kingdoms};
+use crate::lakes::place_lakes;
+use crate::names::{Name, name_world};
+use crate::render::{test_png_hm, test_png_vec};
+use crate::rivers::{pick_river_starts, draw_rivers, draw_rivers_downhill};
+use crate::roads::build_roads;
+use crate::sites::{place_bridges, place_caves, place_towns, place_towns_scored};
+use crate::terrain::{classify_terrain, paint_forest_desert, paint_swamps, paint_dunes, transition_tiles, paint_depths};
+use crate::tile::{Tilemap, Tilelist};
+
+// Everything produced by a run of the generator
+pub struct World {
+    pub seed: u64,
+    pub tilelist: Arc<Tilelist>,
+    pub tilemap: Tilemap,
+    pub heightmap: Heightmap<f32>,
+    pub river_map: Vec<Vec<bool>>,
+    pub lake_map: Vec<Vec<bool>>,
+    pub road_map: Vec<Vec<bool>>,
+    pub climate: Option<Climate>,
+    pub connectivity: Connectivity,
+    pub dungeons: Vec<Dungeon>,
+    pub interiors: Vec<Interior>,
+    pub names: Vec<Name>,
+    pub kingdoms: Kingdoms,
+    pub warnings: Vec<Warning>,
+}
+
+pub fn generate_world(config: &Config) -> Result<World, Error> {
+
+    config.validate()?;
+
+    let width = config.width;
+    let height = config.height;
+    let wrap = config.wrap;
+    let debug_dir = config.debug_dir.as_deref();
+
+    let mut rng = ChaCha8Rng::seed_from_u64(config.seed);
+
+    let tilelist = Arc::new(Tilelist::old_school());
+
+    //// Generate main heightmap
+
+    let mut heightmap = build_heightmap(width, height, wrap, &config.terrain, &config.layout, &mut rng, debug_dir)?;
+
+    if config.erode {
+
+        erode(&mut heightmap, width, height, wrap, &config.terrain, &config.erosion, &mut rng);
+
+        if let Some(dir) = debug_dir {
+            test_png_hm(&heightmap, width, height, dir, "test11")?;
+        }
+    }
+
+    // Here we begin populating tilemap.
+    // First determine water, grass, hill and mountain based on heightmap
+
+    let mut tilemap = Tilemap::new(width, height, "grass", &tilelist);
+
+    classify_terrain(&mut tilemap, &heightmap, width, height, &config.terrain);
+
+    // The climate needs to know where lakes and rivers are, so it paints 
+    // biomes once they are in
+    if !config.climate {
+        paint_forest_desert(&mut tilemap, width, height, wrap, &config.biomes, &mut rng, debug_dir)?;
+        paint_swamps(&mut tilemap, width, height, wrap, &config.biomes, &mut rng);
+    }
+
+    let mut warnings = vec![];
+
+    let mut river_map = vec![vec![false; height as usize]; width as usize];
+    let mut lake_map = vec![vec![false; height as usize]; width as usize];
+
+    // Lakes come before the dunes so their shores get sand too, and before 
+    // rivers so rivers can end in them
+    if config.lakes {
+
+        lake_map = place_lakes(
+            &mut tilemap, &heightmap, &mut river_map, width, height, wrap, &config.terrain, &config.lake, &mut rng
+        );
+
+        if let Some(dir) = debug_dir {
+            test_png_vec(&lake_map, width, height, dir, "test10")?;
+        }
+    }
+
+    paint_dunes(&mut tilemap, width, height, wrap, &config.biomes, &mut rng);
+
+    if config.rivers {
+
+        let river_starts = pick_river_starts(&heightmap, width, height, &config.terrain, &mut rng);
+
+        if river_starts.is_empty() {
+            warnings.push(Warning::NoRoom { what: "rivers" });
+        }
+        let new_rivers = match config.river.mode {
+            RiverMode::RandomWalk => draw_rivers(&mut tilemap, &river_starts, width, height, wrap, &mut rng),
+            RiverMode::Downhill => draw_rivers_downhill(
+                &mut tilemap, &heightmap, &river_starts, width, height, wrap, &config.terrain, config.river.fill_pits
+            ),
+        };
+
+        for (column, new_column) in river_map.iter_mut().zip(new_rivers) {
+            for (cell, new_cell) in column.iter_mut().zip(new_column) {
+                *cell |= new_cell;
+            }
+        }
+
+        if let Some(dir) = debug_dir {
+            test_png_vec(&river_map, width, height, dir, "test9")?;
+        }
+
+        // With roads, bridges are built where roads cross rivers instead
+        if config.bridges && !config.roads {
+            let warning = place_bridges(
+                &mut tilemap, &river_map, river_starts.len(), width, height, wrap, &config.sites, &mut rng
+            );
+
+            warnings.extend(warning);
+        }
+    }
+
+    let mut climate = None;
+
+    if config.climate {
+
+        let climate_map = compute_climate(
+            &tilemap, &heightmap, width, height, wrap, &config.terrain, &config.climate_model, &mut rng, debug_dir
+        )?;
+
+        paint_climate(
+            &mut tilemap, &heightmap, &climate_map, width, height, &config.terrain, &config.climate_model, &mut rng
+        );
+
+        climate = Some(climate_map);
+
+    } else if config.polar {
+        paint_polar(&mut tilemap, &heightmap, width, height, wrap, &config.terrain, &config.climate_model, &mut rng);
+    }
+
+    if config.caves {
+        let warning = place_caves(&mut tilemap, width, height, wrap, &mut rng);
+
+        warnings.extend(warning);
+    }
+
+    if config.towns {
+        match config.sites.placement {
+            TownPlacement::Random => warnings.extend(place_towns(&mut tilemap, width, height, wrap, &config.sites, &mut rng)),
+            TownPlacement::Scored => warnings.extend(place_towns_scored(&mut tilemap, &river_map, width, height, wrap, &config.sites, &mut rng)),
+        }
+    }
+
+    let connectivity = if config.repair {
+
+        let (connectivity, unreachable) = repair_connectivity(&mut tilemap, &river_map, wrap, &config.connectivity, &mut rng);
+
+        warnings.extend(unreachable);
+        connectivity
+
+    } else {
+        Connectivity::analyze(&tilemap, wrap, config.connectivity.start)
+    };
+
+    let mut road_map = vec![vec![false; height as usize]; width as usize];
+
+    if config.roads {
+        road_map = build_roads(&mut tilemap, &river_map, width, height, wrap, &config.road, config.bridges);
+    }
+
+    transition_tiles(&mut tilemap, width, height, wrap);
+
+    if config.depths {
+        paint_depths(&mut tilemap, &heightmap, width, height, wrap, &config.terrain, &config.ocean, &mut rng);
+    }
+
+    let mut dungeons = vec![];
+
+    if config.dungeons {
+        dungeons = build_dungeons(&tilemap, &config.dungeon, &mut rng);
+    }
+
+    let mut interiors = vec![];
+
+    if config.interiors {
+        interiors = build_interiors(&tilemap, wrap, &config.interior, &mut rng);
+    }
+
+    let mut names = vec![];
+
+    if config.names {
+        names = name_world(&tilemap, &river_map, &lake_map, wrap, &config.naming, config.seed);
+    }
+
+    // Comes after naming so kingdoms are called after their capitals
+    let mut kingdoms = Kingdoms { kingdoms: vec![], map: vec![vec![None; height as usize]; width as usize] };
+
+    if config.kingdoms {
+        kingdoms = build_kingdoms(&tilemap, &river_map, wrap, &names, &config.kingdom, &mut rng);
+    }
+
+    Ok(World { seed: config.seed, tilelist, tilemap, heightmap, river_map, lake_map, road_map, climate, connectivity, dungeons, interiors, names, kingdoms, warnings })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::mapfile::save_map;
+
+    // Same seed, same .map file, byte for byte
+    #[test]
+    fn same_seed_same_map() {
+
+        let config = Config { seed: 42, width: 129, height: 129, roads: true, repair: true, ..Config::default() };
+        let path = |name: &str| std::env::temp_dir().join(format!("{}_{}.map", name, std::process::id()));
+        let paths = [path("same_seed_a"), path("same_seed_b")];
+
+        for path in paths.iter() {
+            save_map(&generate_world(&config).unwrap().tilemap, config.seed, path).unwrap();
+        }
+
+        let a = std::fs::read(&paths[0]).unwrap();
+        let b = std::fs::read(&paths[1]).unwrap();
+
+        for path in paths.iter() {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        assert!(a == b, "two maps generated from seed 42 differ");
+    }
+
+    // Wider than tall, with every stage on so any [x][y] mixup goes out of 
+    // bounds
+    #[test]
+    fn non_square_size() {
+
+        let config = Config {
+            seed: 7, width: 160, height: 64, erode: true, climate: true, depths: true, lakes: true, roads: true, 
+            repair: true, dungeons: true, interiors: true, names: true, kingdoms: true, ..Config::default()
+        };
+
+        let world = generate_world(&config).unwrap();
+
+        assert_eq!((world.tilemap.width(), world.tilemap.height()), (160, 64));
+        assert_eq!((world.river_map.len(), world.river_map[0].len()), (160, 64));
+        assert_eq!((world.kingdoms.map.len(), world.kingdoms.map[0].len()), (160, 64));
+    }
+}